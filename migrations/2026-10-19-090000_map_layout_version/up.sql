@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE public.map_layout
+ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
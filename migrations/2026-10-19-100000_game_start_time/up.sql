@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE public.game
+ADD COLUMN start_time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
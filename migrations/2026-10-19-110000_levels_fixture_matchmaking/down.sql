@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.levels_fixture DROP COLUMN matchmaking_mode;
+
+DROP TYPE matchmaking_mode;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TYPE matchmaking_mode AS ENUM ('trophy_nearest', 'base_strength', 'random');
+
+ALTER TABLE public.levels_fixture
+ADD COLUMN matchmaking_mode matchmaking_mode NOT NULL DEFAULT 'trophy_nearest';
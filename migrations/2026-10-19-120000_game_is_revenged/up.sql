@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE public.game ADD COLUMN is_revenged BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE "user" ADD COLUMN shield_until TIMESTAMP;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE public.challenge;
+
+DROP TYPE challenge_status;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TYPE challenge_status AS ENUM ('pending', 'accepted', 'declined', 'completed');
+
+CREATE TABLE public.challenge (
+    id SERIAL PRIMARY KEY,
+    challenger_id INTEGER NOT NULL,
+    opponent_id INTEGER NOT NULL,
+    status challenge_status NOT NULL DEFAULT 'pending',
+    trophy_stakes INTEGER NOT NULL DEFAULT 0,
+    game_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT challenger_id_fk FOREIGN KEY (challenger_id) REFERENCES public.user(id),
+    CONSTRAINT opponent_id_fk FOREIGN KEY (opponent_id) REFERENCES public.user(id),
+    CONSTRAINT game_id_fk FOREIGN KEY (game_id) REFERENCES public.game(id) ON DELETE SET NULL
+);
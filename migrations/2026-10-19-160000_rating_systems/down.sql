@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "user"
+DROP COLUMN rating_deviation,
+DROP COLUMN rating_volatility;
+
+ALTER TABLE public.levels_fixture
+DROP COLUMN rating_system,
+ALTER COLUMN rating_factor SET DEFAULT 0.4;
+
+DROP TYPE rating_system;
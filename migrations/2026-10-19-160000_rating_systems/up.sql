@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TYPE rating_system AS ENUM ('modified_elo', 'glicko2');
+
+-- rating_factor was never read before, it now holds the K-factor of the round's rating system
+ALTER TABLE public.levels_fixture
+ADD COLUMN rating_system rating_system NOT NULL DEFAULT 'modified_elo',
+ALTER COLUMN rating_factor SET DEFAULT 20.0;
+
+UPDATE public.levels_fixture SET rating_factor = 20.0;
+
+ALTER TABLE "user"
+ADD COLUMN rating_deviation REAL NOT NULL DEFAULT 350.0,
+ADD COLUMN rating_volatility REAL NOT NULL DEFAULT 0.06;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE public.rating_history;
+
+DROP TYPE rating_change_reason;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TYPE rating_change_reason AS ENUM ('game', 'challenge', 'admin_reset', 'invalid_base_penalty');
+
+CREATE TABLE public.rating_history (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    old_rating INTEGER NOT NULL,
+    new_rating INTEGER NOT NULL,
+    reason rating_change_reason NOT NULL,
+    game_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT user_id_fk FOREIGN KEY (user_id) REFERENCES public.user(id),
+    CONSTRAINT game_id_fk FOREIGN KEY (game_id) REFERENCES public.game(id)
+);
+
+CREATE INDEX rating_history_user_id_created_at ON public.rating_history (user_id, created_at);
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE public.season_result;
+
+ALTER TABLE public.levels_fixture
+DROP COLUMN reset_factor,
+DROP COLUMN is_rolled_over;
+
+DELETE FROM public.rating_history WHERE reason = 'season_reset';
+ALTER TYPE rating_change_reason RENAME TO rating_change_reason_old;
+CREATE TYPE rating_change_reason AS ENUM ('game', 'challenge', 'admin_reset', 'invalid_base_penalty');
+ALTER TABLE public.rating_history
+ALTER COLUMN reason TYPE rating_change_reason USING reason::text::rating_change_reason;
+DROP TYPE rating_change_reason_old;
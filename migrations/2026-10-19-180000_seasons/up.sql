@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TYPE rating_change_reason ADD VALUE 'season_reset';
+
+ALTER TABLE public.levels_fixture
+ADD COLUMN reset_factor REAL NOT NULL DEFAULT 0.5,
+ADD COLUMN is_rolled_over BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE public.season_result (
+    level_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    final_rank INTEGER NOT NULL,
+    final_trophies INTEGER NOT NULL,
+    reset_trophies INTEGER NOT NULL,
+    reward_artifacts INTEGER NOT NULL,
+    CONSTRAINT season_result_primary PRIMARY KEY (level_id, user_id),
+    CONSTRAINT level_id_fk FOREIGN KEY (level_id) REFERENCES public.levels_fixture(id),
+    CONSTRAINT user_id_fk FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
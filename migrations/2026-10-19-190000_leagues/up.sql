@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE public.league (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    division INTEGER NOT NULL,
+    min_trophies INTEGER NOT NULL UNIQUE
+);
+
+INSERT INTO public.league (name, division, min_trophies) VALUES
+('Bronze', 3, 0),
+('Bronze', 2, 600),
+('Bronze', 1, 800),
+('Silver', 3, 1000),
+('Silver', 2, 1150),
+('Silver', 1, 1300),
+('Gold', 3, 1450),
+('Gold', 2, 1600),
+('Gold', 1, 1750),
+('Champion', 1, 2000);
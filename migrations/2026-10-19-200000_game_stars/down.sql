@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.levels_fixture
+DROP COLUMN one_star_damage,
+DROP COLUMN bank_star,
+DROP COLUMN three_star_damage;
+
+ALTER TABLE public.game DROP COLUMN stars;
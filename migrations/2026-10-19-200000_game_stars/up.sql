@@ -0,0 +1,13 @@
+-- Your SQL goes here
+ALTER TABLE public.game ADD COLUMN stars INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE public.levels_fixture
+ADD COLUMN one_star_damage INTEGER NOT NULL DEFAULT 50,
+ADD COLUMN bank_star BOOLEAN NOT NULL DEFAULT true,
+ADD COLUMN three_star_damage INTEGER NOT NULL DEFAULT 100;
+
+-- bank destruction was never recorded, so finished games only get their damage stars
+UPDATE public.game
+SET stars = (CASE WHEN damage_done >= 50 THEN 1 ELSE 0 END)
+    + (CASE WHEN damage_done >= 100 THEN 1 ELSE 0 END)
+WHERE is_game_over = true;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE public.artifact_ledger;
+
+DROP TYPE artifact_ledger_reason;
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+CREATE TYPE artifact_ledger_reason AS ENUM (
+    'opening_balance',
+    'initial',
+    'loot',
+    'upgrade',
+    'transfer',
+    'grant',
+    'season_reward',
+    'adjustment'
+);
+
+-- every row moves amount artifacts from one account to another, a NULL user being the system
+-- account that artifacts are minted from and spent into. Map space ids are kept for reference
+-- only, as map spaces are recreated whenever a base is saved
+CREATE TABLE public.artifact_ledger (
+    id SERIAL PRIMARY KEY,
+    from_user_id INTEGER,
+    from_map_space_id INTEGER,
+    to_user_id INTEGER,
+    to_map_space_id INTEGER,
+    amount INTEGER NOT NULL,
+    reason artifact_ledger_reason NOT NULL,
+    reference_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT from_user_id_fk FOREIGN KEY (from_user_id) REFERENCES public.user(id),
+    CONSTRAINT to_user_id_fk FOREIGN KEY (to_user_id) REFERENCES public.user(id),
+    CONSTRAINT amount_positive CHECK (amount > 0),
+    CONSTRAINT has_user CHECK (from_user_id IS NOT NULL OR to_user_id IS NOT NULL)
+);
+
+CREATE INDEX artifact_ledger_from_user_id ON public.artifact_ledger (from_user_id);
+CREATE INDEX artifact_ledger_to_user_id ON public.artifact_ledger (to_user_id);
+
+INSERT INTO public.artifact_ledger (to_user_id, amount, reason)
+SELECT id, artifacts, 'opening_balance'
+FROM public.user
+WHERE artifacts > 0;
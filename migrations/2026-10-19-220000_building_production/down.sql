@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+-- postgres can't drop a value from an enum, so 'production' stays in artifact_ledger_reason and
+-- up.sql only adds it when it's missing
+DROP TABLE public.production;
+
+ALTER TABLE public.building_type DROP COLUMN production_rate;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- artifacts produced per hour, 0 for buildings that only store artifacts
+ALTER TABLE public.building_type ADD COLUMN production_rate INTEGER NOT NULL DEFAULT 0;
+
+-- production of a building that hasn't been collected yet, accrued up to updated_at
+CREATE TABLE public.production(
+    map_space_id INTEGER NOT NULL,
+    uncollected INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT production_id_primary PRIMARY KEY(map_space_id),
+    CONSTRAINT map_space_id_fk FOREIGN KEY (map_space_id) REFERENCES public.map_spaces(id) ON DELETE CASCADE
+);
+
+ALTER TYPE artifact_ledger_reason ADD VALUE IF NOT EXISTS 'production';
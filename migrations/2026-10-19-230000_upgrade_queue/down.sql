@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE public.upgrade_queue;
+
+ALTER TABLE public.levels_fixture
+DROP COLUMN upgrade_minutes_per_level,
+DROP COLUMN is_upgrading_defended,
+DROP COLUMN speed_up_cost_per_minute;
+
+ALTER TABLE public.user DROP COLUMN builders;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+ALTER TABLE public.user ADD COLUMN builders INTEGER NOT NULL DEFAULT 2;
+
+-- an upgrade to level n takes n * upgrade_minutes_per_level minutes, 0 keeps upgrades instant
+ALTER TABLE public.levels_fixture
+ADD COLUMN upgrade_minutes_per_level INTEGER NOT NULL DEFAULT 30,
+ADD COLUMN is_upgrading_defended BOOLEAN NOT NULL DEFAULT true,
+ADD COLUMN speed_up_cost_per_minute INTEGER NOT NULL DEFAULT 2;
+
+-- from_id and to_id are block_type, attacker_type or emp_type ids depending on the category
+CREATE TABLE public.upgrade_queue (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    category item_category NOT NULL,
+    from_id INTEGER NOT NULL,
+    to_id INTEGER NOT NULL,
+    cost INTEGER NOT NULL,
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finishes_at TIMESTAMP NOT NULL,
+    is_complete BOOLEAN NOT NULL DEFAULT false,
+    CONSTRAINT user_id_fk FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
+
+CREATE INDEX upgrade_queue_pending ON public.upgrade_queue (user_id) WHERE is_complete = false;
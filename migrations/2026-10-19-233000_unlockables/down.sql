@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- postgres can't drop a value from an enum, so 'unlock' stays in artifact_ledger_reason and up.sql
+-- only adds it when it's missing
+DROP TABLE public.unlockable;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- item types players can buy in the shop, exactly one of the type ids is set. Designers can add
+-- rows or switch is_active at any point in the season
+CREATE TABLE public.unlockable (
+    id SERIAL PRIMARY KEY,
+    category item_category NOT NULL,
+    block_type_id INTEGER,
+    attacker_type_id INTEGER,
+    emp_type_id INTEGER,
+    cost INTEGER NOT NULL,
+    min_trophies INTEGER NOT NULL DEFAULT 0,
+    min_bank_level INTEGER NOT NULL DEFAULT 1,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    CONSTRAINT block_type_id_fk FOREIGN KEY (block_type_id) REFERENCES public.block_type(id),
+    CONSTRAINT attacker_type_id_fk FOREIGN KEY (attacker_type_id) REFERENCES public.attacker_type(id),
+    CONSTRAINT emp_type_id_fk FOREIGN KEY (emp_type_id) REFERENCES public.emp_type(id),
+    CONSTRAINT one_item CHECK (num_nonnulls(block_type_id, attacker_type_id, emp_type_id) = 1),
+    CONSTRAINT non_negative_cost CHECK (cost >= 0)
+);
+
+ALTER TYPE artifact_ledger_reason ADD VALUE IF NOT EXISTS 'unlock';
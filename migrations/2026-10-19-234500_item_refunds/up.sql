@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TYPE artifact_ledger_reason ADD VALUE IF NOT EXISTS 'refund';
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.game DROP COLUMN challenge_id;
+
+UPDATE public.challenge SET status = 'accepted' WHERE status = 'in_progress';
+ALTER TABLE public.challenge ALTER COLUMN status DROP DEFAULT;
+ALTER TYPE challenge_status RENAME TO challenge_status_old;
+CREATE TYPE challenge_status AS ENUM ('pending', 'accepted', 'declined', 'completed');
+ALTER TABLE public.challenge
+ALTER COLUMN status TYPE challenge_status USING status::text::challenge_status;
+ALTER TABLE public.challenge ALTER COLUMN status SET DEFAULT 'pending';
+DROP TYPE challenge_status_old;
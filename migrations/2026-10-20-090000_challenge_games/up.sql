@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TYPE challenge_status ADD VALUE IF NOT EXISTS 'in_progress';
+
+ALTER TABLE public.game
+ADD COLUMN challenge_id INTEGER,
+ADD CONSTRAINT challenge_id_fk FOREIGN KEY (challenge_id) REFERENCES public.challenge(id);
+
+UPDATE public.game SET challenge_id = challenge.id
+FROM public.challenge
+WHERE challenge.game_id = game.id;
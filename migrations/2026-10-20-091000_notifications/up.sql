@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE public.notification (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    is_read BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT user_id_fk FOREIGN KEY (user_id) REFERENCES public.user(id)
+);
@@ -0,0 +1,334 @@
+use super::util::get_game_id_from_redis;
+use crate::api::league::util::{fetch_league_for_trophies, LeagueResponse};
+use crate::api::RedisConn;
+use crate::constants::*;
+use crate::error::DieselError;
+use crate::models::{ItemCategory, MatchmakingMode};
+use crate::schema::{available_blocks, block_type, building_type, defender_type, game, user};
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::HashMap;
+
+sql_function!(fn random() -> diesel::sql_types::Double);
+
+pub struct MatchDecision {
+    pub opponent_id: i32,
+    pub reason: String,
+}
+
+pub trait MatchmakingStrategy {
+    fn name(&self) -> &'static str;
+
+    fn find_opponent(
+        &self,
+        attacker_id: i32,
+        conn: &mut PgConnection,
+        redis_conn: &mut RedisConn,
+    ) -> Result<Option<MatchDecision>>;
+}
+
+pub fn strategy_for(mode: MatchmakingMode) -> Box<dyn MatchmakingStrategy> {
+    match mode {
+        MatchmakingMode::TrophyNearest => Box::new(TrophyNearest),
+        MatchmakingMode::BaseStrength => Box::new(BaseStrength),
+        MatchmakingMode::Random => Box::new(RandomOpponent),
+    }
+}
+
+// Picks a random available opponent from a trophy window that widens on every attempt,
+// trying the attacker's league first
+pub struct TrophyNearest;
+
+impl MatchmakingStrategy for TrophyNearest {
+    fn name(&self) -> &'static str {
+        "trophy_nearest"
+    }
+
+    fn find_opponent(
+        &self,
+        attacker_id: i32,
+        conn: &mut PgConnection,
+        redis_conn: &mut RedisConn,
+    ) -> Result<Option<MatchDecision>> {
+        let trophies = get_trophies(attacker_id, conn)?;
+        let league = fetch_league_for_trophies(trophies, conn)?;
+
+        for league in leagues_to_try(&league) {
+            for attempt in 0..MATCH_MAKING_ATTEMPTS {
+                let window = trophy_window(attempt);
+                let candidates = get_candidates(
+                    attacker_id,
+                    Some(trophy_range(trophies, window, league)),
+                    Some(MATCH_MAKING_CANDIDATES_PER_ATTEMPT),
+                    conn,
+                )?;
+
+                for candidate in candidates {
+                    if is_available_opponent(candidate, redis_conn)? {
+                        return Ok(Some(MatchDecision {
+                            opponent_id: candidate,
+                            reason: format!(
+                                "within {} trophies of attacker's {} on attempt {}{}",
+                                window,
+                                trophies,
+                                attempt,
+                                league_reason(league)
+                            ),
+                        }));
+                    }
+                }
+
+                log::info!(
+                    "No opponent found for Attacker:{} within {} trophies{}",
+                    attacker_id,
+                    window,
+                    league_reason(league)
+                );
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// Picks the available opponent in the widest trophy window whose unlocked defenders and buildings
+// are closest in total level to the attacker's
+pub struct BaseStrength;
+
+impl MatchmakingStrategy for BaseStrength {
+    fn name(&self) -> &'static str {
+        "base_strength"
+    }
+
+    fn find_opponent(
+        &self,
+        attacker_id: i32,
+        conn: &mut PgConnection,
+        redis_conn: &mut RedisConn,
+    ) -> Result<Option<MatchDecision>> {
+        let trophies = get_trophies(attacker_id, conn)?;
+        let league = fetch_league_for_trophies(trophies, conn)?;
+        let window = trophy_window(MATCH_MAKING_ATTEMPTS - 1);
+
+        for league in leagues_to_try(&league) {
+            // every candidate in the window is scored, not just a random sample
+            let candidates = get_candidates(
+                attacker_id,
+                Some(trophy_range(trophies, window, league)),
+                None,
+                conn,
+            )?;
+
+            let mut user_ids = candidates.clone();
+            user_ids.push(attacker_id);
+            let strengths = get_base_strengths(&user_ids, conn)?;
+            let attacker_strength = strengths.get(&attacker_id).copied().unwrap_or(0);
+
+            let mut candidates: Vec<(i32, i32)> = candidates
+                .into_iter()
+                .map(|id| (id, strengths.get(&id).copied().unwrap_or(0)))
+                .collect();
+            candidates.sort_by_key(|(_, strength)| (strength - attacker_strength).abs());
+
+            for (candidate, strength) in candidates {
+                if is_available_opponent(candidate, redis_conn)? {
+                    return Ok(Some(MatchDecision {
+                        opponent_id: candidate,
+                        reason: format!(
+                            "base strength {} closest to attacker's {} within {} trophies{}",
+                            strength,
+                            attacker_strength,
+                            window,
+                            league_reason(league)
+                        ),
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// Picks any available opponent regardless of trophies, meant for events
+pub struct RandomOpponent;
+
+impl MatchmakingStrategy for RandomOpponent {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn find_opponent(
+        &self,
+        attacker_id: i32,
+        conn: &mut PgConnection,
+        redis_conn: &mut RedisConn,
+    ) -> Result<Option<MatchDecision>> {
+        for _ in 0..MATCH_MAKING_ATTEMPTS {
+            for candidate in get_candidates(
+                attacker_id,
+                None,
+                Some(MATCH_MAKING_CANDIDATES_PER_ATTEMPT),
+                conn,
+            )? {
+                if is_available_opponent(candidate, redis_conn)? {
+                    return Ok(Some(MatchDecision {
+                        opponent_id: candidate,
+                        reason: "random pick among all eligible players".to_string(),
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// Trophy window searched on the given attempt, widening on every retry
+pub fn trophy_window(attempt: i32) -> i32 {
+    MATCH_MAKING_TROPHY_WINDOW + attempt * MATCH_MAKING_TROPHY_WINDOW_STEP
+}
+
+// Same-league search first, then the unrestricted one
+fn leagues_to_try(league: &Option<LeagueResponse>) -> Vec<Option<&LeagueResponse>> {
+    match league {
+        Some(league) => vec![Some(league), None],
+        None => vec![None],
+    }
+}
+
+fn trophy_range(trophies: i32, window: i32, league: Option<&LeagueResponse>) -> (i32, i32) {
+    let (min_trophies, max_trophies) = (trophies - window, trophies + window);
+    match league {
+        Some(league) => (
+            min_trophies.max(league.min_trophies),
+            max_trophies.min(league.max_trophies.unwrap_or(i32::MAX)),
+        ),
+        None => (min_trophies, max_trophies),
+    }
+}
+
+fn league_reason(league: Option<&LeagueResponse>) -> String {
+    match league {
+        Some(league) => format!(" in league {} {}", league.name, league.division),
+        None => String::new(),
+    }
+}
+
+fn get_trophies(attacker_id: i32, conn: &mut PgConnection) -> Result<i32> {
+    let trophies = user::table
+        .find(attacker_id)
+        .select(user::trophies)
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(trophies)
+}
+
+fn get_candidates(
+    attacker_id: i32,
+    trophy_range: Option<(i32, i32)>,
+    limit: Option<i64>,
+    conn: &mut PgConnection,
+) -> Result<Vec<i32>> {
+    let now = chrono::Local::now().naive_local();
+    let cooldown_start = now - chrono::Duration::hours(RECENT_OPPONENT_COOLDOWN_IN_HOURS);
+
+    let recent_opponents = game::table
+        .filter(game::attack_id.eq(attacker_id))
+        .filter(game::start_time.gt(cooldown_start))
+        .filter(game::is_practice.eq(false))
+        .select(game::defend_id);
+
+    let mut query = user::table
+        .filter(user::is_pragyan.eq(false))
+        .filter(user::id.ne(attacker_id))
+        .filter(user::id.ne_all(recent_opponents))
+        .filter(user::shield_until.is_null().or(user::shield_until.le(now)))
+        .into_boxed();
+
+    if let Some((min_trophies, max_trophies)) = trophy_range {
+        query = query.filter(user::trophies.between(min_trophies, max_trophies));
+    }
+
+    if let Some(limit) = limit {
+        query = query.limit(limit);
+    }
+
+    let candidates = query
+        .order_by(random())
+        .select(user::id)
+        .load::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(candidates)
+}
+
+// Sum of the levels of every block each user has unlocked, where a defender block counts its
+// defender's level and any other block its building's level
+fn get_base_strengths(user_ids: &[i32], conn: &mut PgConnection) -> Result<HashMap<i32, i32>> {
+    let levels = available_blocks::table
+        .inner_join(
+            block_type::table
+                .inner_join(building_type::table)
+                .left_join(defender_type::table),
+        )
+        .filter(available_blocks::user_id.eq_any(user_ids))
+        .filter(available_blocks::category.eq(ItemCategory::Block))
+        .select((
+            available_blocks::user_id,
+            building_type::level,
+            defender_type::level.nullable(),
+        ))
+        .load::<(i32, i32, Option<i32>)>(conn)
+        .map_err(|err| DieselError {
+            table: "available_blocks",
+            function: function!(),
+            error: err,
+        })?;
+
+    let mut strengths = HashMap::new();
+    for (user_id, building_level, defender_level) in levels {
+        *strengths.entry(user_id).or_insert(0) += defender_level.unwrap_or(building_level);
+    }
+    Ok(strengths)
+}
+
+fn is_available_opponent(opponent_id: i32, redis_conn: &mut RedisConn) -> Result<bool> {
+    if let Some(game_id) = get_game_id_from_redis(opponent_id, redis_conn, false)? {
+        log::info!("Opponent:{} is already in game:{}", opponent_id, game_id);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// Matches the attacker using the strategy configured for the current round
+pub fn find_opponent(
+    attacker_id: i32,
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+) -> Result<Option<i32>> {
+    let mode = crate::api::util::get_current_levels_fixture(conn)?.matchmaking_mode;
+    let strategy = strategy_for(mode);
+
+    let decision = strategy.find_opponent(attacker_id, conn, redis_conn)?;
+    if let Some(decision) = &decision {
+        log::info!(
+            "Matchmaking [{}] chose Opponent:{} for Attacker:{}: {}",
+            strategy.name(),
+            decision.opponent_id,
+            attacker_id,
+            decision.reason
+        );
+    }
+
+    Ok(decision.map(|decision| decision.opponent_id))
+}
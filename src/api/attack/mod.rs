@@ -1,18 +1,22 @@
 use self::util::{get_valid_road_paths, AttackResponse, GameLog, ResultResponse};
 use super::auth::session::AuthUser;
-use super::defense::shortest_path::run_shortest_paths;
+use super::challenge::util::{
+    claim_challenge, fetch_challenge, link_challenge_game, release_challenge,
+};
+use super::defense::shortest_path::get_shortest_paths;
 use super::defense::util::{
     AttackBaseResponse, DefenseResponse, MineTypeResponseWithoutBlockId, SimulationBaseResponse,
 };
+use super::inventory::builder::{fetch_unavailable_block_types, resolve_upgrades};
 use super::user::util::fetch_user;
 use super::{error, PgPool, RedisPool};
 use crate::api::attack::socket::{BuildingResponse, ResultType, SocketRequest, SocketResponse};
 use crate::api::util::HistoryboardQuery;
-use crate::constants::{GAME_AGE_IN_MINUTES, MAX_BOMBS_PER_ATTACK};
-use crate::models::{AttackerType, User};
+use crate::constants::{GAME_AGE_IN_MINUTES, MAX_BOMBS_PER_ATTACK, REVENGE_WINDOW_IN_HOURS};
+use crate::models::{AttackerType, Challenge, ChallengeStatus, Game, User};
+use crate::validator::pathing::DenseNextHops;
 use crate::validator::state::State;
 use crate::validator::util::{BombType, BuildingDetails, DefenderDetails, MineDetails};
-use crate::validator::util::{Coords, SourceDestXY};
 use actix_rt;
 use actix_web::error::ErrorBadRequest;
 use actix_web::web::{Data, Json};
@@ -25,13 +29,17 @@ use crate::validator::game_handler;
 use actix_ws::Message;
 use futures_util::stream::StreamExt;
 
-mod rating;
+mod matchmaking;
+pub mod rating;
 pub mod socket;
 pub mod util;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("").route(web::get().to(init_attack)))
         .service(web::resource("/start").route(web::get().to(socket_handler)))
+        .service(web::resource("/revenge/{game_id}").route(web::get().to(revenge_attack)))
+        .service(web::resource("/practice/{defender_id}").route(web::get().to(practice_attack)))
+        .service(web::resource("/challenge/{challenge_id}").route(web::get().to(challenge_attack)))
         .service(web::resource("/history").route(web::get().to(attack_history)))
         .service(web::resource("/top").route(web::get().to(get_top_attacks)));
 }
@@ -44,41 +52,24 @@ async fn init_attack(
     let attacker_id = user.0;
 
     log::info!("Attacker:{} is trying to initiate an attack", attacker_id);
-    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
-    if let Ok(check) = util::can_attack_happen(&mut conn, attacker_id, true) {
-        if !check {
-            return Err(ErrorBadRequest("You've reached the max limit of attacks"));
-        }
-    }
-
-    let mut redis_conn = redis_pool
-        .get()
-        .map_err(|err| error::handle_error(err.into()))?;
-
-    //Check if attacker is already in a game
-    if let Ok(Some(_)) = util::get_game_id_from_redis(attacker_id, &mut redis_conn, true) {
-        log::info!("Attacker:{} has an ongoing game", attacker_id);
-        return Err(ErrorBadRequest("Attacker has an ongoing game"));
-    }
-
-    log::info!("Attacker:{} has no ongoing game", attacker_id);
+    check_attacker_can_attack(&pool, &redis_pool, attacker_id)?;
 
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
-    let redis_conn = redis_pool
+    let mut redis_conn = redis_pool
         .get()
         .map_err(|err| error::handle_error(err.into()))?;
 
-    let random_opponent_id = web::block(move || {
-        Ok(util::get_random_opponent_id(
+    let opponent_id = web::block(move || {
+        Ok(matchmaking::find_opponent(
             attacker_id,
             &mut conn,
-            redis_conn,
+            &mut redis_conn,
         )?) as anyhow::Result<Option<i32>>
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
 
-    let opponent_id = if let Some(id) = random_opponent_id {
+    let opponent_id = if let Some(id) = opponent_id {
         id
     } else {
         log::info!("No opponent found for Attacker:{}", attacker_id);
@@ -91,6 +82,255 @@ async fn init_attack(
         attacker_id
     );
 
+    create_attack(pool, attacker_id, opponent_id, false, None).await
+}
+
+async fn revenge_attack(
+    game_id: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let attacker_id = user.0;
+    let game_id = game_id.into_inner();
+
+    log::info!(
+        "Attacker:{} is trying to revenge game:{}",
+        attacker_id,
+        game_id
+    );
+    check_attacker_can_attack(&pool, &redis_pool, attacker_id)?;
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let game = web::block(move || {
+        Ok(util::fetch_game(game_id, &mut conn)?) as anyhow::Result<Option<Game>>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    let game = if let Some(game) = game {
+        game
+    } else {
+        return Err(ErrorBadRequest("Game not found"));
+    };
+
+    if game.defend_id != attacker_id || !game.is_game_over || game.is_practice {
+        return Err(ErrorBadRequest("You can only revenge attacks on your base"));
+    }
+
+    if game.is_revenged {
+        return Err(ErrorBadRequest("You've already revenged this attack"));
+    }
+
+    let revenge_window_start =
+        chrono::Local::now().naive_local() - chrono::Duration::hours(REVENGE_WINDOW_IN_HOURS);
+    if game.start_time < revenge_window_start {
+        return Err(ErrorBadRequest("Revenge window for this attack is over"));
+    }
+
+    let opponent_id = game.attack_id;
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = util::get_game_id_from_redis(opponent_id, &mut redis_conn, false) {
+        log::info!("Opponent:{} is already being attacked", opponent_id);
+        return Err(ErrorBadRequest("Opponent is already being attacked"));
+    }
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let is_shielded =
+        web::block(move || Ok(util::is_shielded(opponent_id, &mut conn)?) as anyhow::Result<bool>)
+            .await?
+            .map_err(|err| error::handle_error(err.into()))?;
+
+    if is_shielded {
+        return Err(ErrorBadRequest("Opponent is under a shield"));
+    }
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let is_claimed = web::block(move || {
+        Ok(util::mark_game_revenged(game_id, &mut conn)?) as anyhow::Result<bool>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    if !is_claimed {
+        return Err(ErrorBadRequest("You've already revenged this attack"));
+    }
+
+    log::info!(
+        "Opponent:{} fixed for revenge by Attacker:{}",
+        opponent_id,
+        attacker_id
+    );
+
+    let response = create_attack(pool.clone(), attacker_id, opponent_id, false, None).await;
+    if response.is_err() {
+        //Giving the revenge back since no game was created for it
+        let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+        web::block(move || {
+            Ok(util::unmark_game_revenged(game_id, &mut conn)?) as anyhow::Result<()>
+        })
+        .await?
+        .map_err(|err| error::handle_error(err.into()))?;
+        log::info!("Revenge claim on game:{} released", game_id);
+    }
+    response
+}
+
+async fn practice_attack(
+    defender_id: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let attacker_id = user.0;
+    let defender_id = defender_id.into_inner();
+
+    log::info!(
+        "Attacker:{} is trying to practice on the base of Defender:{}",
+        attacker_id,
+        defender_id
+    );
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    //Practice games don't count towards the attack limit, only an ongoing game blocks them
+    if let Ok(Some(_)) = util::get_game_id_from_redis(attacker_id, &mut redis_conn, true) {
+        log::info!("Attacker:{} has an ongoing game", attacker_id);
+        return Err(ErrorBadRequest("Attacker has an ongoing game"));
+    }
+
+    create_attack(pool, attacker_id, defender_id, true, None).await
+}
+
+async fn challenge_attack(
+    challenge_id: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let attacker_id = user.0;
+    let challenge_id = challenge_id.into_inner();
+
+    log::info!(
+        "Attacker:{} is trying to attack for challenge:{}",
+        attacker_id,
+        challenge_id
+    );
+    check_attacker_can_attack(&pool, &redis_pool, attacker_id)?;
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let challenge = web::block(move || {
+        Ok(fetch_challenge(challenge_id, &mut conn)?) as anyhow::Result<Option<Challenge>>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    let challenge = match challenge {
+        Some(challenge) if challenge.challenger_id == attacker_id => challenge,
+        _ => return Err(ErrorBadRequest("Challenge not found")),
+    };
+
+    if challenge.status != ChallengeStatus::Accepted {
+        return Err(ErrorBadRequest("Challenge has not been accepted"));
+    }
+
+    let opponent_id = challenge.opponent_id;
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = util::get_game_id_from_redis(opponent_id, &mut redis_conn, false) {
+        log::info!("Opponent:{} is already being attacked", opponent_id);
+        return Err(ErrorBadRequest("Opponent is already being attacked"));
+    }
+
+    //Claiming the challenge first so that only one game can ever be played for it
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let is_claimed =
+        web::block(move || Ok(claim_challenge(challenge_id, &mut conn)?) as anyhow::Result<bool>)
+            .await?
+            .map_err(|err| error::handle_error(err.into()))?;
+
+    if !is_claimed {
+        return Err(ErrorBadRequest("Challenge is already being played"));
+    }
+
+    let response = match create_attack(
+        pool.clone(),
+        attacker_id,
+        opponent_id,
+        false,
+        Some(challenge_id),
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+            web::block(move || {
+                Ok(release_challenge(challenge_id, &mut conn)?) as anyhow::Result<()>
+            })
+            .await?
+            .map_err(|err| error::handle_error(err.into()))?;
+            log::info!("Claim on challenge:{} released", challenge_id);
+            return Err(err);
+        }
+    };
+
+    let game_id = response.game_id;
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    web::block(move || {
+        Ok(link_challenge_game(challenge_id, game_id, &mut conn)?) as anyhow::Result<()>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    log::info!("Game:{} linked to challenge:{}", game_id, challenge_id);
+
+    Ok(response)
+}
+
+//Checking the attack limit and that the attacker is not already in a game
+fn check_attacker_can_attack(
+    pool: &web::Data<PgPool>,
+    redis_pool: &Data<RedisPool>,
+    attacker_id: i32,
+) -> Result<()> {
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    if let Ok(check) = util::can_attack_happen(&mut conn, attacker_id) {
+        if !check {
+            return Err(ErrorBadRequest("You've reached the max limit of attacks"));
+        }
+    }
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    //Check if attacker is already in a game
+    if let Ok(Some(_)) = util::get_game_id_from_redis(attacker_id, &mut redis_conn, true) {
+        log::info!("Attacker:{} has an ongoing game", attacker_id);
+        return Err(ErrorBadRequest("Attacker has an ongoing game"));
+    }
+
+    log::info!("Attacker:{} has no ongoing game", attacker_id);
+
+    Ok(())
+}
+
+//Fetching the opponent's base, creating the game and generating the attack token
+async fn create_attack(
+    pool: web::Data<PgPool>,
+    attacker_id: i32,
+    opponent_id: i32,
+    is_practice: bool,
+    challenge_id: Option<i32>,
+) -> Result<Json<AttackResponse>> {
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
 
     //Fetch base details and shortest paths data
@@ -132,7 +372,14 @@ async fn init_attack(
     //Create game
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let game_id = web::block(move || {
-        Ok(util::add_game(attacker_id, opponent_id, map_id, &mut conn)?) as anyhow::Result<i32>
+        Ok(util::add_game(
+            attacker_id,
+            opponent_id,
+            map_id,
+            is_practice,
+            challenge_id,
+            &mut conn,
+        )?) as anyhow::Result<i32>
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
@@ -145,7 +392,7 @@ async fn init_attack(
     );
 
     //Generate attack token to validate the /attack/start
-    let attack_token = util::encode_attack_token(attacker_id, opponent_id, game_id)
+    let attack_token = util::encode_attack_token(attacker_id, opponent_id, game_id, is_practice)
         .map_err(|err| error::handle_error(err.into()))?;
     let response: AttackResponse = AttackResponse {
         user: user_details,
@@ -217,7 +464,8 @@ async fn socket_handler(
     }
 
     let defender_id = attack_token_data.defender_id;
-    if attacker_id == defender_id {
+    let is_practice = attack_token_data.is_practice;
+    if attacker_id == defender_id && !is_practice {
         log::info!("Attacker:{} is trying to attack himself", attacker_id);
         return Err(ErrorBadRequest("Can't attack yourself"));
     }
@@ -231,7 +479,12 @@ async fn socket_handler(
         return Err(ErrorBadRequest("Attacker has an ongoing game"));
     }
 
-    if let Ok(Some(_)) = util::get_game_id_from_redis(defender_id, &mut redis_conn, false) {
+    if !is_practice
+        && matches!(
+            util::get_game_id_from_redis(defender_id, &mut redis_conn, false),
+            Ok(Some(_))
+        )
+    {
         log::info!("Defender:{} has an ongoing game", defender_id);
         return Err(ErrorBadRequest("Defender has an ongoing game"));
     }
@@ -270,24 +523,45 @@ async fn socket_handler(
     };
 
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
 
     let shortest_paths = web::block(move || {
-        Ok(run_shortest_paths(&mut conn, map_id)?) as anyhow::Result<HashMap<SourceDestXY, Coords>>
+        Ok(get_shortest_paths(&mut conn, &mut redis_conn, map_id)?) as anyhow::Result<DenseNextHops>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    //finish the defender's due upgrades, blocks still upgrading may sit this attack out
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let unavailable_block_types = web::block(move || {
+        resolve_upgrades(defender_id, &mut conn)?;
+        fetch_unavailable_block_types(defender_id, &mut conn)
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
 
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let unavailable = unavailable_block_types.clone();
     let defenders: Vec<DefenderDetails> = web::block(move || {
-        Ok(util::get_defenders(&mut conn, map_id, defender_id)?)
-            as anyhow::Result<Vec<DefenderDetails>>
+        Ok(util::get_defenders(
+            &mut conn,
+            map_id,
+            defender_id,
+            &unavailable,
+        )?) as anyhow::Result<Vec<DefenderDetails>>
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
 
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let mines = web::block(move || {
-        Ok(util::get_mines(&mut conn, map_id)?) as anyhow::Result<Vec<MineDetails>>
+        Ok(util::get_mines(
+            &mut conn,
+            map_id,
+            &unavailable_block_types,
+        )?) as anyhow::Result<Vec<MineDetails>>
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
@@ -352,7 +626,9 @@ async fn socket_handler(
         .get()
         .map_err(|err| error::handle_error(err.into()))?;
 
-    if util::add_game_id_to_redis(attacker_id, defender_id, game_id, redis_conn).is_err() {
+    if util::add_game_id_to_redis(attacker_id, defender_id, game_id, is_practice, redis_conn)
+        .is_err()
+    {
         println!("Cannot add game:{} to redis", game_id);
         return Err(ErrorBadRequest("Internal Server Error"));
     }
@@ -374,6 +650,7 @@ async fn socket_handler(
             nd: 0,
             oa: 0,
             od: 0,
+            s: 0,
         },
     };
 
@@ -1,11 +1,66 @@
-use crate::constants::{HIGHEST_TROPHY, SCALE_FACTOR};
+use crate::constants::{
+    GLICKO2_CONVERGENCE_TOLERANCE, GLICKO2_SCALE, GLICKO2_TAU, HIGHEST_TROPHY, INITIAL_RATING,
+};
+use crate::models::{RatingSystemKind, User};
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerRating {
+    pub rating: i32,
+    pub deviation: f32,
+    pub volatility: f32,
+}
+
+impl From<&User> for PlayerRating {
+    fn from(user: &User) -> Self {
+        PlayerRating {
+            rating: user.trophies,
+            deviation: user.rating_deviation,
+            volatility: user.rating_volatility,
+        }
+    }
+}
+
+pub trait RatingSystem {
+    fn name(&self) -> &'static str;
+
+    // Scores are in [-1, 1], positive for the side that won
+    fn rate(
+        &self,
+        attacker: &PlayerRating,
+        defender: &PlayerRating,
+        attack_score: f32,
+        defence_score: f32,
+    ) -> (PlayerRating, PlayerRating);
+}
+
+pub fn rating_system_for(kind: RatingSystemKind, k_factor: f32) -> Box<dyn RatingSystem> {
+    match kind {
+        RatingSystemKind::ModifiedElo => Box::new(ModifiedElo { k_factor }),
+        RatingSystemKind::Glicko2 => Box::new(Glicko2),
+    }
+}
+
+// Attack and defence scores of a finished game, as used by terminate_game. A win scores by the
+// share of stars taken, a loss by the damage the attacker fell short of
+pub fn game_scores(damage_done: i32, stars: i32, max_stars: i32) -> (f32, f32) {
+    if stars > 0 {
+        let attack_score = stars as f32 / max_stars as f32;
+        (attack_score, -attack_score)
+    } else {
+        (
+            (damage_done - 100) as f32 / 100_f32,
+            (100 - damage_done) as f32 / 100_f32,
+        )
+    }
+}
 
 fn expected_score(player_rating: f32, opponent_rating: f32) -> f32 {
     1.0 / (1.0 + 10_f32.powf((opponent_rating - player_rating) / 400.0))
 }
 
-fn baseline_trophies(ep: f32) -> f32 {
-    (ep + 1.0) * SCALE_FACTOR
+fn baseline_trophies(ep: f32, k_factor: f32) -> f32 {
+    (ep + 1.0) * k_factor
 }
 
 fn trophy_scale(old_attacker_rating: f32, old_defender_rating: f32) -> (f32, f32) {
@@ -22,6 +77,7 @@ pub fn new_rating(
     old_defender_rating: i32,
     attack_score: f32,
     defence_score: f32,
+    k_factor: f32,
 ) -> (i32, i32) {
     let ea = expected_score(old_attacker_rating as f32, old_defender_rating as f32);
     let eb = 1.0 - ea;
@@ -31,16 +87,18 @@ pub fn new_rating(
         trophy_scale(old_attacker_rating as f32, old_defender_rating as f32);
 
     if attack_score > 0.0 {
-        new_attacker_rating = (attack_score * baseline_trophies(eb) * attacker_rating_scale) as i32;
+        new_attacker_rating =
+            (attack_score * baseline_trophies(eb, k_factor) * attacker_rating_scale) as i32;
     } else {
-        new_attacker_rating = (attack_score * baseline_trophies(ea) * attacker_rating_scale) as i32;
+        new_attacker_rating =
+            (attack_score * baseline_trophies(ea, k_factor) * attacker_rating_scale) as i32;
     }
     if defence_score > 0.0 {
         new_defender_rating =
-            (defence_score * baseline_trophies(ea) * defender_rating_scale) as i32;
+            (defence_score * baseline_trophies(ea, k_factor) * defender_rating_scale) as i32;
     } else {
         new_defender_rating =
-            (defence_score * baseline_trophies(eb) * defender_rating_scale) as i32;
+            (defence_score * baseline_trophies(eb, k_factor) * defender_rating_scale) as i32;
     }
 
     new_attacker_rating += old_attacker_rating;
@@ -48,3 +106,133 @@ pub fn new_rating(
 
     (new_attacker_rating, new_defender_rating)
 }
+
+// The trophy formula the game has always used, with the K-factor taken from the round
+pub struct ModifiedElo {
+    pub k_factor: f32,
+}
+
+impl RatingSystem for ModifiedElo {
+    fn name(&self) -> &'static str {
+        "modified_elo"
+    }
+
+    fn rate(
+        &self,
+        attacker: &PlayerRating,
+        defender: &PlayerRating,
+        attack_score: f32,
+        defence_score: f32,
+    ) -> (PlayerRating, PlayerRating) {
+        let (attacker_rating, defender_rating) = new_rating(
+            attacker.rating,
+            defender.rating,
+            attack_score,
+            defence_score,
+            self.k_factor,
+        );
+        (
+            PlayerRating {
+                rating: attacker_rating,
+                ..*attacker
+            },
+            PlayerRating {
+                rating: defender_rating,
+                ..*defender
+            },
+        )
+    }
+}
+
+// Glicko-2 treating every game as its own rating period, centred on the initial rating
+pub struct Glicko2;
+
+impl Glicko2 {
+    fn g(phi: f64) -> f64 {
+        1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+    }
+
+    fn new_volatility(delta: f64, phi: f64, v: f64, sigma: f64) -> f64 {
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - phi * phi - v - ex)
+                / (2.0 * (phi * phi + v + ex) * (phi * phi + v + ex))
+                - (x - a) / (GLICKO2_TAU * GLICKO2_TAU)
+        };
+
+        let mut upper_a = a;
+        let mut upper_b = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * GLICKO2_TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * GLICKO2_TAU
+        };
+
+        let mut f_a = f(upper_a);
+        let mut f_b = f(upper_b);
+        while (upper_b - upper_a).abs() > GLICKO2_CONVERGENCE_TOLERANCE {
+            let c = upper_a + (upper_a - upper_b) * f_a / (f_b - f_a);
+            let f_c = f(c);
+            if f_c * f_b <= 0.0 {
+                upper_a = upper_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.0;
+            }
+            upper_b = c;
+            f_b = f_c;
+        }
+
+        (upper_a / 2.0).exp()
+    }
+
+    fn update(player: &PlayerRating, opponent: &PlayerRating, score: f64) -> PlayerRating {
+        let mu = (player.rating - INITIAL_RATING) as f64 / GLICKO2_SCALE;
+        let phi = player.deviation as f64 / GLICKO2_SCALE;
+        let sigma = player.volatility as f64;
+        let opponent_mu = (opponent.rating - INITIAL_RATING) as f64 / GLICKO2_SCALE;
+        let opponent_phi = opponent.deviation as f64 / GLICKO2_SCALE;
+
+        let g = Self::g(opponent_phi);
+        let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+        let v = 1.0 / (g * g * expected * (1.0 - expected));
+        let delta = v * g * (score - expected);
+
+        let new_sigma = Self::new_volatility(delta, phi, v, sigma);
+        let phi_star = (phi * phi + new_sigma * new_sigma).sqrt();
+        let new_phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi * new_phi * g * (score - expected);
+
+        PlayerRating {
+            rating: (new_mu * GLICKO2_SCALE).round() as i32 + INITIAL_RATING,
+            deviation: (new_phi * GLICKO2_SCALE) as f32,
+            volatility: new_sigma as f32,
+        }
+    }
+}
+
+impl RatingSystem for Glicko2 {
+    fn name(&self) -> &'static str {
+        "glicko2"
+    }
+
+    fn rate(
+        &self,
+        attacker: &PlayerRating,
+        defender: &PlayerRating,
+        attack_score: f32,
+        defence_score: f32,
+    ) -> (PlayerRating, PlayerRating) {
+        // Glicko-2 wants outcomes in [0, 1]
+        let attacker_outcome = (attack_score as f64 + 1.0) / 2.0;
+        let defender_outcome = (defence_score as f64 + 1.0) / 2.0;
+        (
+            Self::update(attacker, defender, attacker_outcome),
+            Self::update(defender, attacker, defender_outcome),
+        )
+    }
+}
@@ -1,13 +1,18 @@
-use crate::api::attack::rating::new_rating;
+use crate::api::attack::rating::{game_scores, rating_system_for, PlayerRating};
 use crate::api::auth::TokenClaims;
+use crate::api::challenge::util::{fetch_challenge, release_challenge, update_challenge_status};
 use crate::api::defense::util::{
-    fetch_map_layout, get_map_details_for_attack, get_map_details_for_simulation,
-    AttackBaseResponse, DefenseResponse, SimulationBaseResponse,
+    accrue_production, fetch_map_layout, get_map_details_for_attack,
+    get_map_details_for_simulation, loot_production, AttackBaseResponse, DefenseResponse,
+    SimulationBaseResponse,
 };
 use crate::api::error::AuthError;
 use crate::api::game::util::UserDetail;
 use crate::api::inventory::util::{get_bank_map_space_id, get_block_id_of_bank, get_user_map_id};
-use crate::api::user::util::fetch_user;
+use crate::api::leaderboard::util::update_leaderboard;
+use crate::api::ledger::util::{move_artifacts, ArtifactAccount};
+use crate::api::notification::util::add_notification;
+use crate::api::user::util::{add_rating_history, fetch_user};
 use crate::api::util::{
     GameHistoryEntry, GameHistoryResponse, HistoryboardEntry, HistoryboardResponse,
 };
@@ -15,8 +20,10 @@ use crate::api::{self, RedisConn};
 use crate::constants::*;
 use crate::error::DieselError;
 use crate::models::{
-    Artifact, AttackerType, AvailableBlocks, BlockCategory, BlockType, BuildingType, DefenderType,
-    EmpType, Game, LevelsFixture, MapLayout, MapSpaces, MineType, NewAttackerPath, NewGame, User,
+    Artifact, ArtifactLedgerReason, AttackerType, AvailableBlocks, BlockCategory, BlockType,
+    BuildingType, Challenge, ChallengeStatus, DefenderType, EmpType, Game, LevelsFixture,
+    MapLayout, MapSpaces, MineType, NewAttackerPath, NewGame, NewRatingHistory, RatingChangeReason,
+    User,
 };
 use crate::schema::user;
 use crate::util::function;
@@ -28,7 +35,6 @@ use chrono;
 use diesel::prelude::*;
 use diesel::PgConnection;
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use rand::seq::IteratorRandom;
 use redis::Commands;
 use std::collections::{HashMap, HashSet};
 use std::env;
@@ -60,6 +66,7 @@ pub struct AttackToken {
     pub game_id: i32,
     pub attacker_id: i32,
     pub defender_id: i32,
+    pub is_practice: bool,
     pub iat: usize,
     pub exp: usize,
 }
@@ -91,6 +98,7 @@ pub struct ResultResponse {
     pub nd: i32, //new_defender_trophies
     pub oa: i32, //old_attacker_trophies
     pub od: i32, //old_defender_trophies
+    pub s: i32,  //stars
 }
 
 #[derive(Serialize, Clone)]
@@ -142,12 +150,15 @@ pub fn add_game(
     attacker_id: i32,
     defender_id: i32,
     map_layout_id: i32,
+    is_practice: bool,
+    challenge_id: Option<i32>,
     conn: &mut PgConnection,
 ) -> Result<i32> {
     use crate::schema::game;
 
     // insert in game table
 
+    let now = chrono::Local::now();
     let new_game = NewGame {
         attack_id: &attacker_id,
         defend_id: &defender_id,
@@ -158,7 +169,10 @@ pub fn add_game(
         damage_done: &0,
         emps_used: &0,
         is_game_over: &false,
-        date: &chrono::Local::now().date_naive(),
+        date: &now.date_naive(),
+        start_time: &now.naive_local(),
+        is_practice: &is_practice,
+        challenge_id: challenge_id.as_ref(),
     };
 
     let inserted_game: Game = diesel::insert_into(game::table)
@@ -170,9 +184,60 @@ pub fn add_game(
             error: err,
         })?;
 
+    if !is_practice {
+        break_shield(attacker_id, conn)?;
+    }
+
     Ok(inserted_game.id)
 }
 
+pub fn fetch_game(game_id: i32, conn: &mut PgConnection) -> Result<Option<Game>> {
+    use crate::schema::game;
+
+    let game = game::table
+        .find(game_id)
+        .first::<Game>(conn)
+        .optional()
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(game)
+}
+
+//marking an incoming attack as revenged, returns false if it was already claimed
+pub fn mark_game_revenged(game_id: i32, conn: &mut PgConnection) -> Result<bool> {
+    use crate::schema::game;
+
+    let updated = diesel::update(game::table)
+        .filter(game::id.eq(game_id))
+        .filter(game::is_revenged.eq(false))
+        .set(game::is_revenged.eq(true))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(updated > 0)
+}
+
+//releasing a revenge claim whose game could not be created
+pub fn unmark_game_revenged(game_id: i32, conn: &mut PgConnection) -> Result<()> {
+    use crate::schema::game;
+
+    diesel::update(game::table.find(game_id))
+        .set(game::is_revenged.eq(false))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
 pub fn fetch_attack_history(
     user_id: i32,
     page: i64,
@@ -182,6 +247,7 @@ pub fn fetch_attack_history(
     use crate::schema::{game, levels_fixture, map_layout};
     let joined_table = game::table
         .filter(game::attack_id.eq(user_id))
+        .filter(game::is_practice.eq(false))
         .inner_join(map_layout::table.inner_join(levels_fixture::table))
         .inner_join(user::table.on(game::defend_id.eq(user::id)));
 
@@ -209,9 +275,11 @@ pub fn fetch_attack_history(
                 damage_percent: game.damage_done,
                 artifacts_taken: game.artifacts_collected,
                 trophies_taken: game.attack_score,
+                stars: game.stars,
                 match_id: game.id,
                 replay_availability: is_replay_available,
                 avatar_id: user.avatar_id,
+                is_revenged: game.is_revenged,
             })
         })
         .collect();
@@ -223,6 +291,7 @@ pub fn fetch_top_attacks(user_id: i32, conn: &mut PgConnection) -> Result<GameHi
     use crate::schema::{game, levels_fixture, map_layout};
 
     let joined_table = game::table
+        .filter(game::is_practice.eq(false))
         .inner_join(map_layout::table.inner_join(levels_fixture::table))
         .inner_join(user::table.on(game::defend_id.eq(user::id)));
     let games_result: Result<Vec<GameHistoryEntry>> = joined_table
@@ -302,96 +371,6 @@ pub struct AttackResponse {
     pub game_id: i32,
 }
 
-pub fn get_random_opponent_id(
-    attacker_id: i32,
-    conn: &mut PgConnection,
-    mut redis_conn: RedisConn,
-) -> Result<Option<i32>> {
-    let sorted_users: Vec<(i32, i32)> = user::table
-        .filter(user::is_pragyan.eq(false))
-        .order_by(user::trophies.asc())
-        .select((user::id, user::trophies))
-        .load::<(i32, i32)>(conn)?;
-
-    if let Some(attacker_index) = sorted_users.iter().position(|(id, _)| *id == attacker_id) {
-        let less_or_equal_trophies = sorted_users
-            .iter()
-            .take(attacker_index)
-            .filter(|(id, _)| *id != attacker_id)
-            .rev()
-            .take(10)
-            .cloned()
-            .collect::<Vec<_>>();
-        let more_or_equal_trophies = sorted_users
-            .iter()
-            .skip(attacker_index + 1)
-            .filter(|(id, _)| *id != attacker_id)
-            .take(10)
-            .cloned()
-            .collect::<Vec<_>>();
-
-        // While the opponent id is not present in redis, keep finding a new opponent
-        let mut attempts: i32 = MATCH_MAKING_ATTEMPTS;
-        let mut random_opponent = if let Ok(opponent) =
-            get_random_opponent(&less_or_equal_trophies, &more_or_equal_trophies)
-        {
-            opponent
-        } else {
-            return Err(anyhow::anyhow!("Failed to find an opponent"));
-        };
-
-        loop {
-            if let Ok(Some(_)) = get_game_id_from_redis(random_opponent, &mut redis_conn, false) {
-                random_opponent =
-                    match get_random_opponent(&less_or_equal_trophies, &more_or_equal_trophies) {
-                        Ok(opponent) => opponent,
-                        Err(_) => return Err(anyhow::anyhow!("Failed to find an opponent")),
-                    };
-            } else if let Ok(check) = can_attack_happen(conn, random_opponent, false) {
-                if !check {
-                    random_opponent =
-                        match get_random_opponent(&less_or_equal_trophies, &more_or_equal_trophies)
-                        {
-                            Ok(opponent) => opponent,
-                            Err(_) => {
-                                return Err(anyhow::anyhow!("Failed to find another opponent"))
-                            }
-                        };
-                } else {
-                    return Ok(Some(random_opponent));
-                }
-            } else {
-                return Err(anyhow::anyhow!("Cannot check if attack can happen now"));
-            }
-
-            attempts += 1;
-            if attempts > 10 {
-                return Err(anyhow::anyhow!(
-                    "Failed to find an opponent despite many attempts"
-                ));
-            }
-        }
-    } else {
-        Err(anyhow::anyhow!("Attacker id not found"))
-    }
-}
-
-pub fn get_random_opponent(
-    less_or_equal_trophies: &[(i32, i32)],
-    more_or_equal_trophies: &[(i32, i32)],
-) -> Result<i32> {
-    if let Some(random_opponent) = less_or_equal_trophies
-        .iter()
-        .chain(more_or_equal_trophies.iter())
-        .map(|&(id, _)| id)
-        .choose(&mut rand::thread_rng())
-    {
-        Ok(random_opponent)
-    } else {
-        Err(anyhow::anyhow!("Failed to find an opponent"))
-    }
-}
-
 pub fn get_opponent_base_details_for_attack(
     defender_id: i32,
     conn: &mut PgConnection,
@@ -420,6 +399,7 @@ pub fn add_game_id_to_redis(
     attacker_id: i32,
     defender_id: i32,
     game_id: i32,
+    is_practice: bool,
     mut redis_conn: RedisConn,
 ) -> Result<()> {
     redis_conn
@@ -430,6 +410,11 @@ pub fn add_game_id_to_redis(
         )
         .map_err(|err| anyhow::anyhow!("Failed to set attacker key: {}", err))?;
 
+    //practice games don't lock the defender's base
+    if is_practice {
+        return Ok(());
+    }
+
     redis_conn
         .set_ex(
             format!("Defender:{}", defender_id),
@@ -462,11 +447,17 @@ pub fn get_game_id_from_redis(
 pub fn delete_game_id_from_redis(
     attacker_id: i32,
     defender_id: i32,
+    is_practice: bool,
     redis_conn: &mut RedisConn,
 ) -> Result<()> {
     redis_conn
         .del(format!("Attacker:{}", attacker_id))
         .map_err(|err| anyhow::anyhow!("Failed to delete attacker key: {}", err))?;
+
+    if is_practice {
+        return Ok(());
+    }
+
     redis_conn
         .del(format!("Defender:{}", defender_id))
         .map_err(|err| anyhow::anyhow!("Failed to delete defender key: {}", err))?;
@@ -474,7 +465,12 @@ pub fn delete_game_id_from_redis(
     Ok(())
 }
 
-pub fn encode_attack_token(attacker_id: i32, defender_id: i32, game_id: i32) -> Result<String> {
+pub fn encode_attack_token(
+    attacker_id: i32,
+    defender_id: i32,
+    game_id: i32,
+    is_practice: bool,
+) -> Result<String> {
     let jwt_secret = env::var("COOKIE_KEY").expect("COOKIE_KEY must be set!");
     let now = chrono::Local::now();
     let iat = now.timestamp() as usize;
@@ -485,6 +481,7 @@ pub fn encode_attack_token(attacker_id: i32, defender_id: i32, game_id: i32) ->
         game_id,
         attacker_id,
         defender_id,
+        is_practice,
         exp,
         iat,
     };
@@ -532,11 +529,17 @@ pub fn decode_attack_token(token: &str) -> Result<AttackToken> {
     Ok(token_data.claims)
 }
 
-pub fn get_mines(conn: &mut PgConnection, map_id: i32) -> Result<Vec<MineDetails>> {
+// unavailable_block_types are left out, see fetch_unavailable_block_types
+pub fn get_mines(
+    conn: &mut PgConnection,
+    map_id: i32,
+    unavailable_block_types: &[i32],
+) -> Result<Vec<MineDetails>> {
     use crate::schema::{block_type, map_spaces, mine_type};
 
     let joined_table = map_spaces::table
         .filter(map_spaces::map_id.eq(map_id))
+        .filter(map_spaces::block_type_id.ne_all(unavailable_block_types))
         .inner_join(block_type::table.inner_join(mine_type::table));
 
     let mines: Vec<MineDetails> = joined_table
@@ -561,6 +564,7 @@ pub fn get_defenders(
     conn: &mut PgConnection,
     map_id: i32,
     user_id: i32,
+    unavailable_block_types: &[i32],
 ) -> Result<Vec<DefenderDetails>> {
     use crate::schema::{available_blocks, block_type, building_type, defender_type, map_spaces};
     let result: Vec<(
@@ -574,6 +578,7 @@ pub fn get_defenders(
                 .inner_join(defender_type::table),
         )
         .filter(map_spaces::map_id.eq(map_id))
+        .filter(map_spaces::block_type_id.ne_all(unavailable_block_types))
         .filter(available_blocks::user_id.eq(user_id))
         .load::<(
             MapSpaces,
@@ -682,6 +687,12 @@ pub fn update_buidling_artifacts(
         artifact_count.insert(map_space.id, artifact.count.into());
     }
 
+    // Uncollected production can be looted along with the stored artifacts
+    for production in accrue_production(conn, map_id)? {
+        *artifact_count.entry(production.map_space_id).or_insert(0) +=
+            production.uncollected as i64;
+    }
+
     // Update the buildings with the artifact count
     for building in buildings.iter_mut() {
         building.artifacts_obtained = *artifact_count.get(&building.id).unwrap_or(&0) as i32;
@@ -696,12 +707,8 @@ pub fn terminate_game(
     damaged_buildings: &[BuildingResponse],
     redis_conn: &mut RedisConn,
 ) -> Result<()> {
-    use crate::schema::{artifact, game};
     let attacker_id = game_log.a.id;
     let defender_id = game_log.d.id;
-    let damage_done = game_log.r.d;
-    let bombs_used = game_log.r.b;
-    let artifacts_collected = game_log.r.a;
     let game_id = game_log.g;
     log::info!(
         "Terminating game for game:{} and attacker:{} and opponent:{}",
@@ -710,11 +717,157 @@ pub fn terminate_game(
         defender_id
     );
 
-    let (attack_score, defense_score) = if damage_done < WIN_THRESHOLD {
-        (damage_done - 100, 100 - damage_done)
-    } else {
-        (damage_done, -damage_done)
+    let game_details = fetch_game(game_id, conn)?.ok_or(anyhow::anyhow!("Game not found"))?;
+
+    //stars, loot and ratings are either all saved or none of them are
+    let settled = conn.transaction(|conn| settle_game(game_log, damaged_buildings, conn));
+
+    //the players are let out of the game even if it was already over or couldn't be settled
+    if delete_game_id_from_redis(
+        attacker_id,
+        defender_id,
+        game_details.is_practice,
+        redis_conn,
+    )
+    .is_err()
+    {
+        log::info!(
+            "Can't remove game:{} and attacker:{} and opponent:{} from redis",
+            game_id,
+            attacker_id,
+            defender_id
+        );
+        return Err(anyhow::anyhow!("Can't remove game from redis"));
+    }
+
+    update_players_on_leaderboard(&settled?, redis_conn);
+
+    log::info!(
+        "Game terminated successfully for game:{} and attacker:{} and opponent:{}",
+        game_id,
+        attacker_id,
+        defender_id
+    );
+
+    Ok(())
+}
+
+//settling the result of a game inside the caller's transaction, returns the players whose
+//trophies changed along with their new trophies
+fn settle_game(
+    game_log: &mut GameLog,
+    damaged_buildings: &[BuildingResponse],
+    conn: &mut PgConnection,
+) -> Result<Vec<(User, i32)>> {
+    use crate::schema::game;
+    let attacker_id = game_log.a.id;
+    let defender_id = game_log.d.id;
+    let damage_done = game_log.r.d;
+    let bombs_used = game_log.r.b;
+    let game_id = game_log.g;
+
+    //locking the game so that it can only be settled once
+    let game_details = game::table
+        .find(game_id)
+        .for_update()
+        .first::<Game>(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+    if game_details.is_game_over {
+        log::info!("Game:{} is already terminated", game_id);
+        return Ok(Vec::new());
+    }
+    let game_levels_fixture = fetch_levels_fixture_of_map(game_details.map_layout_id, conn)?;
+    let bank_destroyed = is_bank_destroyed(
+        defender_id,
+        game_details.map_layout_id,
+        damaged_buildings,
+        conn,
+    )?;
+    let stars = count_stars(damage_done, bank_destroyed, &game_levels_fixture);
+    let total_stars = max_stars(&game_levels_fixture);
+    game_log.r.s = stars;
+
+    log::info!(
+        "Game:{} earned {} of {} stars with {}% damage{}",
+        game_id,
+        stars,
+        total_stars,
+        damage_done,
+        if bank_destroyed {
+            " and the bank destroyed"
+        } else {
+            ""
+        }
+    );
+
+    if game_details.is_practice {
+        return settle_practice_game(game_log, conn);
+    }
+
+    if let Some(challenge_id) = game_details.challenge_id {
+        let challenge = fetch_challenge(challenge_id, conn)?
+            .ok_or_else(|| anyhow::anyhow!("Challenge:{} not found", challenge_id))?;
+        return settle_challenge_game(game_log, &challenge, conn);
+    }
+
+    //the attacker keeps a share of the destroyed buildings' artifacts that grows with the stars,
+    //moved straight into the attacker's bank
+    let attacker_map_id = get_user_map_id(attacker_id, conn)?;
+    let attacker_bank_block_type_id = get_block_id_of_bank(conn, &attacker_id)?;
+    let attacker_bank_map_space_id =
+        get_bank_map_space_id(conn, &attacker_map_id, &attacker_bank_block_type_id)?;
+
+    let attacker_bank = ArtifactAccount::Building {
+        user_id: attacker_id,
+        map_space_id: attacker_bank_map_space_id,
     };
+    let mut artifacts_collected = 0;
+    for building in damaged_buildings {
+        let loot = star_loot(building.artifacts_if_damaged, stars);
+        //uncollected production goes first, it was never part of the defender's artifacts
+        let from_production = match loot_production(conn, building.id, loot, attacker_bank, game_id)
+        {
+            Ok(taken) => taken,
+            Err(err) => {
+                log::info!(
+                    "Failed to loot production of building:{} for game:{}: {:?}",
+                    building.id,
+                    game_id,
+                    err
+                );
+                0
+            }
+        };
+        artifacts_collected += from_production;
+
+        let moved = move_artifacts(
+            ArtifactAccount::Building {
+                user_id: defender_id,
+                map_space_id: building.id,
+            },
+            attacker_bank,
+            loot - from_production,
+            ArtifactLedgerReason::Loot,
+            Some(game_id),
+            conn,
+        );
+        match moved {
+            Ok(()) => artifacts_collected += loot - from_production,
+            Err(err) => log::info!(
+                "Failed to loot building:{} for game:{} and attacker:{} and opponent:{}: {:?}",
+                building.id,
+                game_id,
+                attacker_id,
+                defender_id,
+                err
+            ),
+        }
+    }
+    game_log.r.a = artifacts_collected;
 
     let attacker_details = user::table
         .filter(user::id.eq(attacker_id))
@@ -734,17 +887,29 @@ pub fn terminate_game(
             error: err,
         })?;
 
-    let attack_score = attack_score as f32 / 100_f32;
-    let defence_score = defense_score as f32 / 100_f32;
+    let (attack_score, defence_score) = game_scores(damage_done, stars, total_stars);
 
-    let new_trophies = new_rating(
-        attacker_details.trophies,
-        defender_details.trophies,
+    let rating_system = rating_system_for(
+        game_levels_fixture.rating_system,
+        game_levels_fixture.rating_factor,
+    );
+    let (new_attacker_rating, new_defender_rating) = rating_system.rate(
+        &PlayerRating::from(&attacker_details),
+        &PlayerRating::from(&defender_details),
         attack_score,
         defence_score,
     );
+    let new_trophies = (new_attacker_rating.rating, new_defender_rating.rating);
 
-    //Add bonus trophies (just call the function)
+    log::info!(
+        "Game:{} rated with {}: attacker {} -> {}, defender {} -> {}",
+        game_id,
+        rating_system.name(),
+        attacker_details.trophies,
+        new_trophies.0,
+        defender_details.trophies,
+        new_trophies.1
+    );
 
     game_log.r.oa = attacker_details.trophies;
     game_log.r.od = defender_details.trophies;
@@ -759,6 +924,7 @@ pub fn terminate_game(
             game::attack_score.eq(new_trophies.0 - attacker_details.trophies),
             game::defend_score.eq(new_trophies.1 - defender_details.trophies),
             game::artifacts_collected.eq(artifacts_collected),
+            game::stars.eq(stars),
         ))
         .execute(conn)
         .map_err(|err| DieselError {
@@ -767,16 +933,13 @@ pub fn terminate_game(
             error: err,
         })?;
 
-    let (attacker_wins, defender_wins) = if damage_done < WIN_THRESHOLD {
-        (0, 1)
-    } else {
-        (1, 0)
-    };
+    let (attacker_wins, defender_wins) = if stars > 0 { (1, 0) } else { (0, 1) };
 
     diesel::update(user::table.find(&game_log.a.id))
         .set((
-            user::artifacts.eq(user::artifacts + artifacts_collected),
             user::trophies.eq(user::trophies + new_trophies.0 - attacker_details.trophies),
+            user::rating_deviation.eq(new_attacker_rating.deviation),
+            user::rating_volatility.eq(new_attacker_rating.volatility),
             user::attacks_won.eq(user::attacks_won + attacker_wins),
         ))
         .execute(conn)
@@ -786,18 +949,11 @@ pub fn terminate_game(
             error: err,
         })?;
 
-    if deduct_artifacts_from_building(damaged_buildings.to_vec(), conn).is_err() {
-        log::info!(
-            "Failed to deduct artifacts from building for game:{} and attacker:{} and opponent:{}",
-            game_id,
-            attacker_id,
-            defender_id
-        );
-    }
     diesel::update(user::table.find(&game_log.d.id))
         .set((
-            user::artifacts.eq(user::artifacts - artifacts_collected),
             user::trophies.eq(user::trophies + new_trophies.1 - defender_details.trophies),
+            user::rating_deviation.eq(new_defender_rating.deviation),
+            user::rating_volatility.eq(new_defender_rating.volatility),
             user::defenses_won.eq(user::defenses_won + defender_wins),
         ))
         .execute(conn)
@@ -807,19 +963,43 @@ pub fn terminate_game(
             error: err,
         })?;
 
-    let attacker_map_id = get_user_map_id(attacker_id, conn)?;
-    let attacker_bank_block_type_id = get_block_id_of_bank(conn, &attacker_id)?;
-    let attacker_bank_map_space_id =
-        get_bank_map_space_id(conn, &attacker_map_id, &attacker_bank_block_type_id)?;
+    add_rating_history(
+        conn,
+        &[
+            NewRatingHistory {
+                user_id: attacker_id,
+                old_rating: attacker_details.trophies,
+                new_rating: new_trophies.0,
+                reason: RatingChangeReason::Game,
+                game_id: Some(game_id),
+            },
+            NewRatingHistory {
+                user_id: defender_id,
+                old_rating: defender_details.trophies,
+                new_rating: new_trophies.1,
+                reason: RatingChangeReason::Game,
+                game_id: Some(game_id),
+            },
+        ],
+    )?;
 
-    diesel::update(artifact::table.find(attacker_bank_map_space_id))
-        .set(artifact::count.eq(artifact::count + artifacts_collected))
-        .execute(conn)
-        .map_err(|err| DieselError {
-            table: "artifact",
-            function: function!(),
-            error: err,
-        })?;
+    if let Some(shield_duration) = shield_duration(damage_done) {
+        let shield_until = chrono::Local::now().naive_local() + shield_duration;
+        diesel::update(user::table.find(&game_log.d.id))
+            .set(user::shield_until.eq(shield_until))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+        log::info!(
+            "Defender:{} is shielded until {} after game:{}",
+            defender_id,
+            shield_until,
+            game_id
+        );
+    }
 
     // if let Ok(sim_log) = serde_json::to_string(&game_log) {
     //     let new_simulation_log = NewSimulationLog {
@@ -840,28 +1020,186 @@ pub fn terminate_game(
     //     println!("Done Inserting into similation log, game id: {}", game_id);
     // }
 
-    if delete_game_id_from_redis(game_log.a.id, game_log.d.id, redis_conn).is_err() {
-        log::info!(
-            "Can't remove game:{} and attacker:{} and opponent:{} from redis",
-            game_id,
-            attacker_id,
-            defender_id
-        );
-        return Err(anyhow::anyhow!("Can't remove game from redis"));
-    }
-
     // for event in game_log.events.iter() {
     //     println!("Event: {:?}\n", event);
     // }
 
+    Ok(vec![
+        (attacker_details, new_trophies.0),
+        (defender_details, new_trophies.1),
+    ])
+}
+
+//practice games only record the result, trophies and artifacts are left untouched
+fn settle_practice_game(
+    game_log: &mut GameLog,
+    conn: &mut PgConnection,
+) -> Result<Vec<(User, i32)>> {
+    use crate::schema::game;
+    let attacker_id = game_log.a.id;
+    let defender_id = game_log.d.id;
+    let game_id = game_log.g;
+
+    game_log.r.oa = game_log.a.trophies;
+    game_log.r.od = game_log.d.trophies;
+    game_log.r.na = game_log.a.trophies;
+    game_log.r.nd = game_log.d.trophies;
+
+    diesel::update(game::table.find(game_id))
+        .set((
+            game::damage_done.eq(game_log.r.d),
+            game::is_game_over.eq(true),
+            game::emps_used.eq(game_log.r.b),
+            game::artifacts_collected.eq(game_log.r.a),
+            game::stars.eq(game_log.r.s),
+        ))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+
     log::info!(
-        "Game terminated successfully for game:{} and attacker:{} and opponent:{}",
+        "Practice game settled for game:{} and attacker:{} and opponent:{}",
         game_id,
         attacker_id,
         defender_id
     );
 
-    Ok(())
+    Ok(Vec::new())
+}
+
+//challenge games only move the agreed trophy stakes from the loser to the winner
+fn settle_challenge_game(
+    game_log: &mut GameLog,
+    challenge: &Challenge,
+    conn: &mut PgConnection,
+) -> Result<Vec<(User, i32)>> {
+    use crate::schema::game;
+    let attacker_id = game_log.a.id;
+    let defender_id = game_log.d.id;
+    let damage_done = game_log.r.d;
+    let game_id = game_log.g;
+
+    let stakes = if game_log.r.s > 0 {
+        challenge.trophy_stakes
+    } else {
+        -challenge.trophy_stakes
+    };
+
+    //locking both players, in id order, so the ratings recorded are the ones the stakes apply to
+    let trophies = user::table
+        .filter(user::id.eq_any([attacker_id, defender_id]))
+        .order_by(user::id)
+        .select((user::id, user::trophies))
+        .for_update()
+        .load::<(i32, i32)>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    let trophies_of = |player_id: i32| {
+        trophies
+            .iter()
+            .find(|(id, _)| *id == player_id)
+            .map(|(_, trophies)| *trophies)
+            .ok_or_else(|| anyhow::anyhow!("Player:{} not found", player_id))
+    };
+    game_log.r.oa = trophies_of(attacker_id)?;
+    game_log.r.od = trophies_of(defender_id)?;
+    game_log.r.na = game_log.r.oa + stakes;
+    game_log.r.nd = game_log.r.od - stakes;
+
+    diesel::update(game::table.find(game_id))
+        .set((
+            game::damage_done.eq(damage_done),
+            game::is_game_over.eq(true),
+            game::emps_used.eq(game_log.r.b),
+            game::attack_score.eq(stakes),
+            game::defend_score.eq(-stakes),
+            game::artifacts_collected.eq(0),
+            game::stars.eq(game_log.r.s),
+        ))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+
+    if stakes != 0 {
+        diesel::update(user::table.find(attacker_id))
+            .set(user::trophies.eq(user::trophies + stakes))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+        diesel::update(user::table.find(defender_id))
+            .set(user::trophies.eq(user::trophies - stakes))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+
+        add_rating_history(
+            conn,
+            &[
+                NewRatingHistory {
+                    user_id: attacker_id,
+                    old_rating: game_log.r.oa,
+                    new_rating: game_log.r.na,
+                    reason: RatingChangeReason::Challenge,
+                    game_id: Some(game_id),
+                },
+                NewRatingHistory {
+                    user_id: defender_id,
+                    old_rating: game_log.r.od,
+                    new_rating: game_log.r.nd,
+                    reason: RatingChangeReason::Challenge,
+                    game_id: Some(game_id),
+                },
+            ],
+        )?;
+    }
+
+    update_challenge_status(
+        challenge.id,
+        ChallengeStatus::InProgress,
+        ChallengeStatus::Completed,
+        conn,
+    )?;
+
+    let message = format!(
+        "Challenge between {} and {} is over with {} stars, {} trophies changed hands",
+        game_log.a.username,
+        game_log.d.username,
+        game_log.r.s,
+        stakes.abs()
+    );
+    add_notification(attacker_id, &message, conn)?;
+    add_notification(defender_id, &message, conn)?;
+
+    log::info!(
+        "Challenge:{} completed with game:{} for attacker:{} and opponent:{}",
+        challenge.id,
+        game_id,
+        attacker_id,
+        defender_id
+    );
+
+    if stakes == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(vec![
+        (game_log.a.clone(), game_log.r.na),
+        (game_log.d.clone(), game_log.r.nd),
+    ])
 }
 
 pub fn check_and_remove_incomplete_game(
@@ -897,62 +1235,143 @@ pub fn check_and_remove_incomplete_game(
                 function: function!(),
                 error: err,
             })?;
+
+        //a challenge whose game was abandoned can be attacked again
+        if let Some(pending_challenge_id) = pending_game.challenge_id {
+            release_challenge(pending_challenge_id, conn)?;
+        }
     }
 
     Ok(())
 }
 
-pub fn can_attack_happen(conn: &mut PgConnection, user_id: i32, is_attacker: bool) -> Result<bool> {
+pub fn can_attack_happen(conn: &mut PgConnection, user_id: i32) -> Result<bool> {
     use crate::schema::game::dsl::*;
 
     let current_date = chrono::Local::now().date_naive();
 
-    if is_attacker {
-        let count: i64 = game
-            .filter(attack_id.eq(user_id))
-            .filter(is_game_over.eq(true))
-            .filter(date.eq(current_date))
-            .count()
-            .get_result::<i64>(conn)
-            .map_err(|err| DieselError {
-                table: "game",
-                function: function!(),
-                error: err,
-            })?;
-        Ok(count < TOTAL_ATTACKS_PER_DAY)
+    let count: i64 = game
+        .filter(attack_id.eq(user_id))
+        .filter(is_game_over.eq(true))
+        .filter(is_practice.eq(false))
+        .filter(date.eq(current_date))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|err| DieselError {
+            table: "game",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(count < TOTAL_ATTACKS_PER_DAY)
+}
+
+//postgres stays the source of truth, a failed update is fixed by the next rebuild_leaderboard
+fn update_players_on_leaderboard(players: &[(User, i32)], redis_conn: &mut RedisConn) {
+    let entries: Vec<(i32, i32)> = players
+        .iter()
+        .filter(|(player, _)| !player.is_pragyan)
+        .map(|(player, trophies)| (player.id, *trophies))
+        .collect();
+    if let Err(err) = update_leaderboard(&entries, redis_conn) {
+        log::info!("Failed to update leaderboard for {:?}: {:?}", entries, err);
+    }
+}
+
+// One star for reaching the level's damage threshold, one for destroying the bank when the level
+// awards it and one for reaching the full-destruction threshold
+pub fn count_stars(damage_done: i32, bank_destroyed: bool, levels_fixture: &LevelsFixture) -> i32 {
+    let mut stars = 0;
+    if damage_done >= levels_fixture.one_star_damage {
+        stars += 1;
+    }
+    if levels_fixture.bank_star && bank_destroyed {
+        stars += 1;
+    }
+    if damage_done >= levels_fixture.three_star_damage {
+        stars += 1;
+    }
+    stars
+}
+
+pub fn max_stars(levels_fixture: &LevelsFixture) -> i32 {
+    if levels_fixture.bank_star {
+        3
     } else {
-        let count: i64 = game
-            .filter(defend_id.eq(user_id))
-            .filter(is_game_over.eq(true))
-            .filter(date.eq(current_date))
-            .count()
-            .get_result::<i64>(conn)
-            .map_err(|err| DieselError {
-                table: "game",
-                function: function!(),
-                error: err,
-            })?;
-        Ok(count < TOTAL_ATTACKS_PER_DAY)
+        2
     }
 }
 
-pub fn deduct_artifacts_from_building(
-    damaged_buildings: Vec<BuildingResponse>,
+pub fn star_loot(artifacts: i32, stars: i32) -> i32 {
+    let percentage = STAR_LOOT_PERCENTAGES[stars.clamp(0, 3) as usize];
+    artifacts * percentage / 100
+}
+
+fn fetch_levels_fixture_of_map(
+    map_layout_id: i32,
     conn: &mut PgConnection,
-) -> Result<()> {
-    use crate::schema::artifact;
-    for building in damaged_buildings.iter() {
-        if (building.artifacts_if_damaged) > 0 {
-            diesel::update(artifact::table.find(building.id))
-                .set(artifact::count.eq(artifact::count - building.artifacts_if_damaged))
-                .execute(conn)
-                .map_err(|err| DieselError {
-                    table: "artifact",
-                    function: function!(),
-                    error: err,
-                })?;
-        }
+) -> Result<LevelsFixture> {
+    use crate::schema::{levels_fixture, map_layout};
+
+    let levels_fixture = map_layout::table
+        .find(map_layout_id)
+        .inner_join(levels_fixture::table)
+        .select(levels_fixture::all_columns)
+        .first::<LevelsFixture>(conn)
+        .map_err(|err| DieselError {
+            table: "levels_fixture",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(levels_fixture)
+}
+
+fn is_bank_destroyed(
+    defender_id: i32,
+    map_layout_id: i32,
+    damaged_buildings: &[BuildingResponse],
+    conn: &mut PgConnection,
+) -> Result<bool> {
+    let bank_block_type_id = get_block_id_of_bank(conn, &defender_id)?;
+    let bank_map_space_id = get_bank_map_space_id(conn, &map_layout_id, &bank_block_type_id)?;
+    Ok(damaged_buildings
+        .iter()
+        .any(|building| building.id == bank_map_space_id && building.hp == 0))
+}
+
+//shield length grows with the damage done to the defender's base
+pub fn shield_duration(damage_done: i32) -> Option<chrono::Duration> {
+    if damage_done < SHIELD_DAMAGE_THRESHOLD {
+        return None;
     }
+    Some(chrono::Duration::minutes(
+        damage_done as i64 * SHIELD_MINUTES_PER_DAMAGE,
+    ))
+}
+
+pub fn is_shielded(user_id: i32, conn: &mut PgConnection) -> Result<bool> {
+    let shield_until = user::table
+        .find(user_id)
+        .select(user::shield_until)
+        .first::<Option<chrono::NaiveDateTime>>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(shield_until.is_some_and(|shield_until| shield_until > chrono::Local::now().naive_local()))
+}
+
+//attacking someone gives up whatever is left of the attacker's shield
+pub fn break_shield(user_id: i32, conn: &mut PgConnection) -> Result<()> {
+    diesel::update(user::table.find(user_id))
+        .filter(user::shield_until.is_not_null())
+        .set(user::shield_until.eq(None::<chrono::NaiveDateTime>))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
     Ok(())
 }
 
@@ -1,5 +1,6 @@
 use super::{PgPool, RedisPool};
 use crate::api::error;
+use crate::api::leaderboard::util::join_leaderboard;
 use actix_session::Session;
 use actix_web::web::{self, Data, Json};
 use actix_web::Responder;
@@ -139,6 +140,12 @@ async fn login(
         .set(user.id, device + &expiring_time)
         .map_err(|err| error::handle_error(err.into()))?;
 
+    //rank players created on their first login
+    if !user.is_pragyan {
+        join_leaderboard(user.id, user.trophies, &mut redis_conn)
+            .map_err(|err| error::handle_error(err.into()))?;
+    }
+
     // insert the jwt token in the session cookie
     session
         .insert("token", token.clone())
@@ -0,0 +1,195 @@
+use super::auth::session::AuthUser;
+use super::notification::util::add_notification;
+use super::user::util::fetch_user;
+use super::{error, PgPool};
+use crate::constants::MAX_CHALLENGE_TROPHY_STAKES;
+use crate::models::{Challenge, ChallengeStatus, User};
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::web::{self, Data, Json, Path};
+use actix_web::{Responder, Result};
+use util::NewChallengeRequest;
+
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("")
+            .route(web::get().to(list_challenges))
+            .route(web::post().to(create_challenge)),
+    )
+    .service(web::resource("/{challenge_id}").route(web::get().to(get_challenge)))
+    .service(web::resource("/{challenge_id}/accept").route(web::post().to(accept_challenge)))
+    .service(web::resource("/{challenge_id}/decline").route(web::post().to(decline_challenge)));
+}
+
+async fn create_challenge(
+    request: Json<NewChallengeRequest>,
+    pool: Data<PgPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let challenger_id = user.0;
+    let opponent_id = request.opponent_id;
+    let trophy_stakes = request.trophy_stakes.unwrap_or(0);
+
+    if challenger_id == opponent_id {
+        return Err(ErrorBadRequest("Can't challenge yourself"));
+    }
+
+    if !(0..=MAX_CHALLENGE_TROPHY_STAKES).contains(&trophy_stakes) {
+        return Err(ErrorBadRequest("Invalid trophy stakes"));
+    }
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let opponent =
+        web::block(move || Ok(fetch_user(&mut conn, opponent_id)?) as anyhow::Result<Option<User>>)
+            .await?
+            .map_err(|err| error::handle_error(err.into()))?;
+
+    if opponent.is_none() {
+        return Err(ErrorNotFound("Opponent not found"));
+    }
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let challenge = web::block(move || {
+        let challenge = util::add_challenge(challenger_id, opponent_id, trophy_stakes, &mut conn)?;
+        let challenger = fetch_user(&mut conn, challenger_id)?
+            .ok_or_else(|| anyhow::anyhow!("User:{} not found", challenger_id))?;
+        add_notification(
+            opponent_id,
+            &format!(
+                "{} challenged you with {} trophies at stake",
+                challenger.username, trophy_stakes
+            ),
+            &mut conn,
+        )?;
+        Ok(challenge) as anyhow::Result<Challenge>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    log::info!(
+        "Challenge:{} sent by User:{} to User:{}",
+        challenge.id,
+        challenger_id,
+        opponent_id
+    );
+
+    Ok(Json(challenge))
+}
+
+async fn list_challenges(pool: Data<PgPool>, user: AuthUser) -> Result<impl Responder> {
+    let user_id = user.0;
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_user_challenges(user_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
+
+async fn get_challenge(
+    challenge_id: Path<i32>,
+    pool: Data<PgPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let challenge = fetch_user_challenge(&pool, challenge_id.into_inner(), user_id).await?;
+    Ok(Json(challenge))
+}
+
+async fn accept_challenge(
+    challenge_id: Path<i32>,
+    pool: Data<PgPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    respond_to_challenge(&pool, challenge_id.into_inner(), user.0, true).await?;
+    Ok("Challenge accepted")
+}
+
+async fn decline_challenge(
+    challenge_id: Path<i32>,
+    pool: Data<PgPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    respond_to_challenge(&pool, challenge_id.into_inner(), user.0, false).await?;
+    Ok("Challenge declined")
+}
+
+//Fetching a challenge the user is either side of
+async fn fetch_user_challenge(
+    pool: &Data<PgPool>,
+    challenge_id: i32,
+    user_id: i32,
+) -> Result<Challenge> {
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let challenge = web::block(move || {
+        Ok(util::fetch_challenge(challenge_id, &mut conn)?) as anyhow::Result<Option<Challenge>>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    match challenge {
+        Some(challenge)
+            if challenge.challenger_id == user_id || challenge.opponent_id == user_id =>
+        {
+            Ok(challenge)
+        }
+        _ => Err(ErrorNotFound("Challenge not found")),
+    }
+}
+
+async fn respond_to_challenge(
+    pool: &Data<PgPool>,
+    challenge_id: i32,
+    user_id: i32,
+    is_accepted: bool,
+) -> Result<()> {
+    let challenge = fetch_user_challenge(pool, challenge_id, user_id).await?;
+    if challenge.opponent_id != user_id {
+        return Err(ErrorBadRequest("Only the challenged player can respond"));
+    }
+
+    let status = if is_accepted {
+        ChallengeStatus::Accepted
+    } else {
+        ChallengeStatus::Declined
+    };
+
+    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let challenger_id = challenge.challenger_id;
+    let is_updated = web::block(move || {
+        let is_updated = util::update_challenge_status(
+            challenge_id,
+            ChallengeStatus::Pending,
+            status,
+            &mut conn,
+        )?;
+        if is_updated {
+            let opponent = fetch_user(&mut conn, user_id)?
+                .ok_or_else(|| anyhow::anyhow!("User:{} not found", user_id))?;
+            let response = if is_accepted { "accepted" } else { "declined" };
+            add_notification(
+                challenger_id,
+                &format!("{} {} your challenge", opponent.username, response),
+                &mut conn,
+            )?;
+        }
+        Ok(is_updated) as anyhow::Result<bool>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    if !is_updated {
+        return Err(ErrorBadRequest("Challenge is no longer pending"));
+    }
+
+    log::info!(
+        "Challenge:{} is {:?} by User:{}",
+        challenge_id,
+        status,
+        user_id
+    );
+
+    Ok(())
+}
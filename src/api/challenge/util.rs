@@ -0,0 +1,143 @@
+use crate::error::DieselError;
+use crate::models::{Challenge, ChallengeStatus, NewChallenge};
+use crate::schema::challenge;
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct NewChallengeRequest {
+    pub opponent_id: i32,
+    pub trophy_stakes: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ChallengesResponse {
+    pub sent: Vec<Challenge>,
+    pub received: Vec<Challenge>,
+}
+
+pub fn add_challenge(
+    challenger_id: i32,
+    opponent_id: i32,
+    trophy_stakes: i32,
+    conn: &mut PgConnection,
+) -> Result<Challenge> {
+    let new_challenge = NewChallenge {
+        challenger_id: &challenger_id,
+        opponent_id: &opponent_id,
+        trophy_stakes: &trophy_stakes,
+    };
+
+    let challenge = diesel::insert_into(challenge::table)
+        .values(&new_challenge)
+        .get_result::<Challenge>(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(challenge)
+}
+
+pub fn fetch_challenge(challenge_id: i32, conn: &mut PgConnection) -> Result<Option<Challenge>> {
+    let challenge = challenge::table
+        .find(challenge_id)
+        .first::<Challenge>(conn)
+        .optional()
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(challenge)
+}
+
+pub fn fetch_user_challenges(user_id: i32, conn: &mut PgConnection) -> Result<ChallengesResponse> {
+    let sent = challenge::table
+        .filter(challenge::challenger_id.eq(user_id))
+        .order_by(challenge::created_at.desc())
+        .load::<Challenge>(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+
+    let received = challenge::table
+        .filter(challenge::opponent_id.eq(user_id))
+        .order_by(challenge::created_at.desc())
+        .load::<Challenge>(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+
+    Ok(ChallengesResponse { sent, received })
+}
+
+//moving a challenge from one status to another, returns false if it was no longer in the expected status
+pub fn update_challenge_status(
+    challenge_id: i32,
+    from: ChallengeStatus,
+    to: ChallengeStatus,
+    conn: &mut PgConnection,
+) -> Result<bool> {
+    let updated = diesel::update(challenge::table.find(challenge_id))
+        .filter(challenge::status.eq(from))
+        .set(challenge::status.eq(to))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(updated > 0)
+}
+
+//claiming an accepted challenge for a single game, returns false if it's no longer open to attack
+pub fn claim_challenge(challenge_id: i32, conn: &mut PgConnection) -> Result<bool> {
+    let updated = diesel::update(challenge::table.find(challenge_id))
+        .filter(challenge::status.eq(ChallengeStatus::Accepted))
+        .filter(challenge::game_id.is_null())
+        .set(challenge::status.eq(ChallengeStatus::InProgress))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(updated > 0)
+}
+
+//opening a claimed challenge to attack again once its game is gone
+pub fn release_challenge(challenge_id: i32, conn: &mut PgConnection) -> Result<()> {
+    diesel::update(challenge::table.find(challenge_id))
+        .filter(challenge::status.eq(ChallengeStatus::InProgress))
+        .set((
+            challenge::status.eq(ChallengeStatus::Accepted),
+            challenge::game_id.eq(None::<i32>),
+        ))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
+pub fn link_challenge_game(challenge_id: i32, game_id: i32, conn: &mut PgConnection) -> Result<()> {
+    diesel::update(challenge::table.find(challenge_id))
+        .set(challenge::game_id.eq(game_id))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "challenge",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use super::shortest_path::get_shortest_paths;
+use super::util::{
+    distribute_artifacts, fetch_map_layout, lock_user_artifacts, ArtifactDistributionResponse,
+    BuildingArtifacts,
+};
+use super::ArtifactDistributionEntry;
+use crate::api::RedisConn;
+use crate::constants::{AUTO_DISTRIBUTION_DEFENDER_WEIGHT, BANK_BUILDING_NAME, MAP_SIZE, ROAD_ID};
+use crate::error::DieselError;
+use crate::models::BlockCategory;
+use crate::util::function;
+use crate::validator::pathing::{DenseNextHops, Pathing};
+use crate::validator::util::Coords;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionStrategy {
+    // Deepest buildings first, counting road hops from the entrances and defenders in range
+    Protect,
+    // As close to the same count in every building as capacities allow
+    Even,
+    // Largest buildings first
+    Capacity,
+}
+
+#[derive(Serialize)]
+pub struct AutoDistributionResponse {
+    pub strategy: DistributionStrategy,
+    pub applied: bool,
+    #[serde(flatten)]
+    pub distribution: ArtifactDistributionResponse,
+}
+
+struct StorageBuilding {
+    map_space_id: i32,
+    capacity: i32,
+    artifacts: i32,
+    is_bank: bool,
+    tile: Coords,
+    width: i32,
+}
+
+struct DefenderRange {
+    tile: Coords,
+    width: i32,
+    radius: i32,
+}
+
+struct BaseLayout {
+    buildings: Vec<StorageBuilding>,
+    defenders: Vec<DefenderRange>,
+}
+
+fn fetch_base_layout(conn: &mut PgConnection, map_id: i32) -> Result<BaseLayout> {
+    use crate::schema::{artifact, block_type, building_type, defender_type, map_spaces};
+
+    let blocks = map_spaces::table
+        .inner_join(
+            block_type::table
+                .inner_join(building_type::table)
+                .left_join(defender_type::table),
+        )
+        .left_join(artifact::table)
+        .filter(map_spaces::map_id.eq(map_id))
+        .filter(building_type::id.ne(ROAD_ID))
+        .select((
+            map_spaces::id,
+            map_spaces::x_coordinate,
+            map_spaces::y_coordinate,
+            block_type::category,
+            building_type::name,
+            building_type::width,
+            building_type::capacity,
+            defender_type::radius.nullable(),
+            artifact::count.nullable(),
+        ))
+        .load::<(
+            i32,
+            i32,
+            i32,
+            BlockCategory,
+            String,
+            i32,
+            i32,
+            Option<i32>,
+            Option<i32>,
+        )>(conn)
+        .map_err(|err| DieselError {
+            table: "map_spaces",
+            function: function!(),
+            error: err,
+        })?;
+
+    let mut layout = BaseLayout {
+        buildings: Vec::new(),
+        defenders: Vec::new(),
+    };
+    for (map_space_id, x, y, category, name, width, capacity, radius, count) in blocks {
+        let tile = Coords { x, y };
+        match (category, radius) {
+            (BlockCategory::Building, _) if capacity > 0 => {
+                layout.buildings.push(StorageBuilding {
+                    map_space_id,
+                    capacity,
+                    artifacts: count.unwrap_or(0),
+                    is_bank: name == BANK_BUILDING_NAME,
+                    tile,
+                    width,
+                })
+            }
+            (BlockCategory::Defender, Some(radius)) => layout.defenders.push(DefenderRange {
+                tile,
+                width,
+                radius,
+            }),
+            _ => {}
+        }
+    }
+    Ok(layout)
+}
+
+fn center(tile: Coords, width: i32) -> (f32, f32) {
+    (
+        tile.x as f32 + width as f32 / 2.0,
+        tile.y as f32 + width as f32 / 2.0,
+    )
+}
+
+// Number of road tiles walked from source to dest, None if dest can't be reached
+fn road_distance(shortest_paths: &DenseNextHops, source: Coords, dest: Coords) -> Option<i32> {
+    let mut current = source;
+    let mut distance = 0;
+    while current != dest {
+        current = shortest_paths.next_hop(current, dest)?;
+        distance += 1;
+        if distance as usize > shortest_paths.roads().len() {
+            return None;
+        }
+    }
+    Some(distance)
+}
+
+// How hard a building is to reach: road hops from the nearest entrance to the building plus a
+// bonus for every defender that has it in range. Entrances are the roads on the edge of the map,
+// without any the distance to the edge is used instead
+fn protection_scores(layout: &BaseLayout, shortest_paths: &DenseNextHops) -> Vec<i32> {
+    let edge = MAP_SIZE as i32 - 1;
+    let entrances: Vec<Coords> = shortest_paths
+        .roads()
+        .iter()
+        .filter(|road| road.x == 0 || road.y == 0 || road.x == edge || road.y == edge)
+        .copied()
+        .collect();
+
+    layout
+        .buildings
+        .iter()
+        .map(|building| {
+            let Coords { x, y } = building.tile;
+            let width = building.width;
+            let adjacent_roads = (0..width).flat_map(|i| {
+                [
+                    Coords { x: x + i, y: y - 1 },
+                    Coords {
+                        x: x + i,
+                        y: y + width,
+                    },
+                    Coords { x: x - 1, y: y + i },
+                    Coords {
+                        x: x + width,
+                        y: y + i,
+                    },
+                ]
+            });
+            let depth = adjacent_roads
+                .filter(|road| shortest_paths.index_of(*road).is_some())
+                .flat_map(|road| {
+                    entrances
+                        .iter()
+                        .filter_map(move |entrance| road_distance(shortest_paths, *entrance, road))
+                })
+                .min()
+                .unwrap_or_else(|| x.min(y).min(edge - x - width + 1).min(edge - y - width + 1));
+
+            let (building_x, building_y) = center(building.tile, building.width);
+            let defenders_in_range = layout
+                .defenders
+                .iter()
+                .filter(|defender| {
+                    let (defender_x, defender_y) = center(defender.tile, defender.width);
+                    (defender_x - building_x).hypot(defender_y - building_y)
+                        <= defender.radius as f32
+                })
+                .count() as i32;
+
+            depth + defenders_in_range * AUTO_DISTRIBUTION_DEFENDER_WEIGHT
+        })
+        .collect()
+}
+
+// Fills the buildings to capacity in the given order until there are no artifacts left
+fn fill_in_order(capacities: &[i32], order: &[usize], total: i32) -> Vec<i32> {
+    let mut counts = vec![0; capacities.len()];
+    let mut remaining = total;
+    for &index in order {
+        counts[index] = capacities[index].min(remaining);
+        remaining -= counts[index];
+    }
+    counts
+}
+
+// Splits the artifacts evenly, handing what a full building can't take to the others
+fn fill_evenly(capacities: &[i32], total: i32) -> Vec<i32> {
+    let mut counts = vec![0; capacities.len()];
+    let mut remaining = total;
+    loop {
+        let open: Vec<usize> = (0..capacities.len())
+            .filter(|index| counts[*index] < capacities[*index])
+            .collect();
+        if remaining == 0 || open.is_empty() {
+            return counts;
+        }
+        let share = (remaining / open.len() as i32).max(1);
+        for index in open {
+            let added = share.min(capacities[index] - counts[index]).min(remaining);
+            counts[index] += added;
+            remaining -= added;
+        }
+    }
+}
+
+fn propose_distribution(
+    layout: &BaseLayout,
+    strategy: DistributionStrategy,
+    shortest_paths: &DenseNextHops,
+) -> Vec<ArtifactDistributionEntry> {
+    let total: i32 = layout
+        .buildings
+        .iter()
+        .map(|building| building.artifacts)
+        .sum();
+    let capacities: Vec<i32> = layout
+        .buildings
+        .iter()
+        .map(|building| building.capacity)
+        .collect();
+
+    let mut order: Vec<usize> = (0..layout.buildings.len()).collect();
+    let counts = match strategy {
+        DistributionStrategy::Protect => {
+            let scores = protection_scores(layout, shortest_paths);
+            order.sort_by_key(|index| std::cmp::Reverse(scores[*index]));
+            fill_in_order(&capacities, &order, total)
+        }
+        DistributionStrategy::Even => fill_evenly(&capacities, total),
+        DistributionStrategy::Capacity => {
+            order.sort_by_key(|index| std::cmp::Reverse(capacities[*index]));
+            fill_in_order(&capacities, &order, total)
+        }
+    };
+
+    layout
+        .buildings
+        .iter()
+        .zip(counts)
+        .map(|(building, artifacts)| ArtifactDistributionEntry {
+            map_space_id: building.map_space_id,
+            artifacts,
+        })
+        .collect()
+}
+
+// Proposes a distribution of the artifacts already in the user's base, applying it if apply is set
+pub fn auto_distribute_artifacts(
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+    user_id: i32,
+    strategy: DistributionStrategy,
+    apply: bool,
+) -> Result<AutoDistributionResponse> {
+    let map_id = fetch_map_layout(conn, &user_id)?.id;
+    let shortest_paths = get_shortest_paths(conn, redis_conn, map_id)?;
+
+    conn.transaction(|conn| {
+        if apply {
+            lock_user_artifacts(conn, user_id)?;
+        }
+        let layout = fetch_base_layout(conn, map_id)?;
+        let proposal = propose_distribution(&layout, strategy, &shortest_paths);
+
+        let distribution = if apply {
+            distribute_artifacts(conn, user_id, &proposal)?
+        } else {
+            let bank_map_space_id = layout
+                .buildings
+                .iter()
+                .find(|building| building.is_bank)
+                .map(|building| building.map_space_id);
+            let mut distribution = ArtifactDistributionResponse {
+                bank_map_space_id: bank_map_space_id.unwrap_or(0),
+                artifacts_in_bank: 0,
+                buildings: Vec::new(),
+            };
+            for entry in proposal {
+                if Some(entry.map_space_id) == bank_map_space_id {
+                    distribution.artifacts_in_bank = entry.artifacts;
+                } else {
+                    distribution.buildings.push(BuildingArtifacts {
+                        map_space_id: entry.map_space_id,
+                        artifacts: entry.artifacts,
+                    });
+                }
+            }
+            distribution
+                .buildings
+                .sort_by_key(|building| building.map_space_id);
+            distribution
+        };
+
+        Ok(AutoDistributionResponse {
+            strategy,
+            applied: apply,
+            distribution,
+        })
+    })
+}
@@ -1,3 +1,4 @@
+use self::distribution::DistributionStrategy;
 use self::util::{DefenderTypeResponse, MineTypeResponse};
 
 use super::attack::util::get_game_id_from_redis;
@@ -6,7 +7,7 @@ use super::inventory::util::get_user_artifacts;
 use super::user::util::fetch_user;
 use super::PgPool;
 use super::RedisPool;
-use crate::api::error;
+use crate::api::error::{self, ArtifactDistributionError, BaseInvalidError};
 use crate::api::util::HistoryboardQuery;
 use crate::models::*;
 use actix_web::error::{ErrorBadRequest, ErrorNotFound};
@@ -15,6 +16,7 @@ use actix_web::{Responder, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod distribution;
 pub mod shortest_path;
 pub mod util;
 mod validate;
@@ -27,6 +29,12 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
     )
     .service(web::resource("/top").route(web::get().to(get_top_defenses)))
     .service(web::resource("/transfer").route(web::post().to(post_transfer_artifacts)))
+    .service(web::resource("/distribute").route(web::post().to(post_distribute_artifacts)))
+    .service(
+        web::resource("/distribute/auto").route(web::post().to(post_auto_distribute_artifacts)),
+    )
+    .service(web::resource("/production").route(web::get().to(get_production)))
+    .service(web::resource("/collect").route(web::post().to(post_collect_production)))
     .service(web::resource("/save").route(web::put().to(confirm_base_details)))
     .service(web::resource("/game/{id}").route(web::get().to(get_game_base_details)))
     .service(web::resource("/history").route(web::get().to(defense_history)))
@@ -48,6 +56,18 @@ pub struct TransferArtifactEntry {
     pub map_space_id: i32,
 }
 
+#[derive(Deserialize)]
+pub struct ArtifactDistributionEntry {
+    pub map_space_id: i32,
+    pub artifacts: i32,
+}
+
+#[derive(Deserialize)]
+pub struct AutoDistributionRequest {
+    pub strategy: DistributionStrategy,
+    pub apply: Option<bool>,
+}
+
 #[derive(Serialize)]
 pub struct TransferArtifactResponse {
     pub building_map_space_id: i32,
@@ -176,10 +196,10 @@ async fn post_transfer_artifacts(
     web::block(move || {
         util::transfer_artifacts_building(
             &mut conn,
+            &user_id,
             &transfer.map_space_id,
             &bank_map_space_id,
-            &new_building_artifact_count,
-            &new_bank_artifact_count,
+            &transfer.artifacts_differ,
         )
     })
     .await?
@@ -193,6 +213,118 @@ async fn post_transfer_artifacts(
     }))
 }
 
+async fn post_distribute_artifacts(
+    distribution: Json<Vec<ArtifactDistributionEntry>>,
+    pg_pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest(
+            "You are under attack. Cannot transfer artifacts",
+        ));
+    }
+
+    let distribution = distribution.into_inner();
+    let response = web::block(move || {
+        let mut conn = pg_pool.get()?;
+        util::distribute_artifacts(&mut conn, user_id, &distribution)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<ArtifactDistributionError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(response))
+}
+
+async fn post_auto_distribute_artifacts(
+    request: Json<AutoDistributionRequest>,
+    pg_pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let strategy = request.strategy;
+    let apply = request.apply.unwrap_or(false);
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if apply {
+        if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+            return Err(ErrorBadRequest(
+                "You are under attack. Cannot transfer artifacts",
+            ));
+        }
+    }
+
+    let response = web::block(move || {
+        let mut conn = pg_pool.get()?;
+        distribution::auto_distribute_artifacts(
+            &mut conn,
+            &mut redis_conn,
+            user_id,
+            strategy,
+            apply,
+        )
+    })
+    .await?
+    .map_err(|err| match err.downcast::<ArtifactDistributionError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(response))
+}
+
+async fn get_production(pool: Data<PgPool>, user: AuthUser) -> Result<impl Responder> {
+    let user_id = user.0;
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_production(&mut conn, user_id)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    Ok(Json(response))
+}
+
+async fn post_collect_production(
+    pg_pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest(
+            "You are under attack. Cannot collect artifacts",
+        ));
+    }
+
+    let response = web::block(move || {
+        let mut conn = pg_pool.get()?;
+        util::collect_production(&mut conn, user_id)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    Ok(Json(response))
+}
+
 async fn get_user_base_details(pool: Data<PgPool>, user: AuthUser) -> Result<impl Responder> {
     let defender_id = user.0;
     let response = web::block(move || {
@@ -289,7 +421,10 @@ async fn set_base_details(
         util::put_base_details(&map_spaces, &map, &mut conn)
     })
     .await?
-    .map_err(|err| error::handle_error(err.into()))?;
+    .map_err(|err| match err.downcast::<BaseInvalidError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
 
     Ok("Updated successfully")
 }
@@ -349,12 +484,24 @@ async fn confirm_base_details(
 
     web::block(move || {
         let mut conn = pool.get()?;
-        util::put_base_details(&map_spaces, &map, &mut conn)
-        // util::calculate_shortest_paths(&mut conn, map.id)?;
+        util::put_base_details(&map_spaces, &map, &mut conn)?;
         // util::set_map_valid(&mut conn, map.id)
+
+        //Precompute shortest paths so that attacks on this layout don't have to
+        if shortest_path::cache_shortest_paths(&mut conn, &mut redis_conn, map.id).is_err() {
+            log::info!(
+                "Failed to cache shortest paths for map:{} of defender:{}",
+                map.id,
+                defender_id
+            );
+        }
+        Ok(()) as anyhow::Result<()>
     })
     .await?
-    .map_err(|err| error::handle_error(err.into()))?;
+    .map_err(|err| match err.downcast::<BaseInvalidError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
 
     Ok("Saved successfully")
 }
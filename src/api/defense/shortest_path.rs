@@ -1,22 +1,91 @@
+use crate::api::RedisConn;
 use crate::constants::*;
 use crate::error::DieselError;
-use crate::schema::{block_type, map_spaces};
+use crate::schema::{block_type, map_layout, map_spaces};
 use crate::util::function;
-use crate::validator::util::{Coords, SourceDestXY};
+use crate::validator::pathing::DenseNextHops;
+use crate::validator::util::Coords;
 use anyhow::Result;
 use array2d::Array2D;
 use diesel::prelude::*;
 use diesel::RunQueryDsl;
 use diesel::{PgConnection, QueryDsl};
-use std::collections::{HashMap, HashSet, VecDeque};
+use redis::Commands;
+use std::collections::VecDeque;
 
 const NO_BLOCK: i32 = -1;
 
+fn shortest_paths_key(map_id: i32, version: i32) -> String {
+    format!("ShortestPaths:{}:{}", map_id, version)
+}
+
+fn get_layout_version(conn: &mut PgConnection, map_id: i32) -> Result<i32> {
+    let version = map_layout::table
+        .find(map_id)
+        .select(map_layout::version)
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "map_layout",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(version)
+}
+
+//computing the shortest paths of the current layout and storing them in redis
+pub fn cache_shortest_paths(
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+    map_id: i32,
+) -> Result<DenseNextHops> {
+    let version = get_layout_version(conn, map_id)?;
+    let shortest_paths = run_shortest_paths(conn, map_id)?;
+
+    redis_conn
+        .set_ex::<_, _, ()>(
+            shortest_paths_key(map_id, version),
+            shortest_paths.encode(),
+            SHORTEST_PATHS_CACHE_AGE_IN_DAYS * 24 * 60 * 60,
+        )
+        .map_err(|err| anyhow::anyhow!("Failed to set shortest paths key: {}", err))?;
+
+    if version > 0 {
+        redis_conn
+            .del::<_, ()>(shortest_paths_key(map_id, version - 1))
+            .map_err(|err| anyhow::anyhow!("Failed to delete shortest paths key: {}", err))?;
+    }
+
+    Ok(shortest_paths)
+}
+
+//loading the shortest paths of the current layout, computing them if they are not cached
+pub fn get_shortest_paths(
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+    map_id: i32,
+) -> Result<DenseNextHops> {
+    let version = get_layout_version(conn, map_id)?;
+    let cached: Option<Vec<u8>> = redis_conn
+        .get(shortest_paths_key(map_id, version))
+        .map_err(|err| anyhow::anyhow!("Failed to get shortest paths key: {}", err))?;
+
+    if let Some(shortest_paths) = cached.and_then(|encoded| DenseNextHops::decode(&encoded)) {
+        return Ok(shortest_paths);
+    }
+
+    log::info!(
+        "Shortest paths for map:{} version:{} not cached",
+        map_id,
+        version
+    );
+    cache_shortest_paths(conn, redis_conn, map_id)
+}
+
 //running shortest path simulation
 pub fn run_shortest_paths(
     conn: &mut PgConnection,
     input_map_layout_id: i32,
-) -> Result<HashMap<SourceDestXY, Coords>> {
+) -> Result<DenseNextHops> {
     let roads_list: Vec<(i32, i32)> = map_spaces::table
         .inner_join(block_type::table)
         .filter(map_spaces::map_id.eq(input_map_layout_id))
@@ -39,77 +108,54 @@ pub fn run_shortest_paths(
             .unwrap();
     }
 
-    let mut adjacency_list: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    let mut shortest_paths =
+        DenseNextHops::new(roads_list.iter().map(|&(x, y)| Coords { x, y }).collect());
 
-    for road in &roads_list {
-        let (road_x, road_y) = (road.0, road.1);
+    let mut adjacency_list: Vec<Vec<usize>> = Vec::with_capacity(roads_list.len());
+
+    for road in shortest_paths.roads() {
         let mut neighbors = Vec::new();
 
         for &(dx, dy) in &[(1, 0), (0, 1), (-1, 0), (0, -1)] {
-            let (nx, ny) = (road_x + dx, road_y + dy);
+            let (nx, ny) = (road.x + dx, road.y + dy);
             if nx >= 0
                 && ny >= 0
                 && (nx as usize) < MAP_SIZE
                 && (ny as usize) < MAP_SIZE
                 && graph_2d[(nx as usize, ny as usize)] == ROAD_ID
             {
-                neighbors.push((nx, ny));
+                if let Some(neighbor) = shortest_paths.index_of(Coords { x: nx, y: ny }) {
+                    neighbors.push(neighbor);
+                }
             }
         }
 
-        adjacency_list.insert((road_x, road_y), neighbors);
+        adjacency_list.push(neighbors);
     }
 
-    let mut shortest_paths: HashMap<SourceDestXY, Coords> = HashMap::new();
-
-    for (start_x, start_y) in &roads_list {
-        let start_node = (*start_x, *start_y);
-        let mut visited: HashSet<(i32, i32)> = HashSet::new();
-        let mut queue: VecDeque<((i32, i32), (i32, i32))> = VecDeque::new();
+    for start_node in 0..roads_list.len() {
+        let mut visited = vec![false; roads_list.len()];
+        let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
 
-        visited.insert(start_node);
+        visited[start_node] = true;
         queue.push_back((start_node, start_node));
 
         while let Some((current_node, parent_node)) = queue.pop_front() {
-            for neighbor in &adjacency_list[&current_node] {
-                if visited.insert(*neighbor) {
+            for &neighbor in &adjacency_list[current_node] {
+                if !visited[neighbor] {
+                    visited[neighbor] = true;
                     let next_hop = if start_node == parent_node {
-                        *neighbor
+                        neighbor
                     } else {
                         parent_node
                     };
 
-                    queue.push_back((*neighbor, next_hop));
-
-                    shortest_paths.insert(
-                        SourceDestXY {
-                            source_x: *start_x,
-                            source_y: *start_y,
-                            dest_x: neighbor.0,
-                            dest_y: neighbor.1,
-                        },
-                        Coords {
-                            x: next_hop.0,
-                            y: next_hop.1,
-                        },
-                    );
+                    queue.push_back((neighbor, next_hop));
+                    shortest_paths.set_next_hop(start_node, neighbor, next_hop);
                 }
             }
         }
     }
 
-    // // Writing entries to shortest_path table
-    // let chunks: Vec<&[NewShortestPath]> = shortest_paths.chunks(1000).collect();
-    // for chunk in chunks {
-    //     diesel::insert_into(shortest_path::table)
-    //         .values(chunk)
-    //         .execute(conn)
-    //         .map_err(|err| DieselError {
-    //             table: "shortest_path",
-    //             function: function!(),
-    //             error: err,
-    //         })?;
-    // }
-
     Ok(shortest_paths)
 }
@@ -1,18 +1,23 @@
 /// CRUD functions
-use super::MapSpacesEntry;
+use super::{ArtifactDistributionEntry, MapSpacesEntry};
 use crate::api::auth::LoginResponse;
-use crate::api::error::AuthError;
+use crate::api::error::{ArtifactDistributionError, AuthError, BaseInvalidError};
 use crate::api::game::util::UserDetail;
+use crate::api::ledger::util::{add_ledger_entry, move_artifacts, ArtifactAccount};
 use crate::api::user::util::fetch_user;
 use crate::api::util::GameHistoryEntry;
 use crate::api::util::{HistoryboardEntry, HistoryboardResponse};
 use crate::api::{self};
-use crate::constants::{BANK_BUILDING_NAME, INITIAL_ARTIFACTS, INITIAL_RATING, ROAD_ID};
+use crate::constants::{
+    BANK_BUILDING_NAME, INITIAL_ARTIFACTS, INITIAL_RATING, PRODUCTION_INTERVAL_SECONDS, ROAD_ID,
+};
 use crate::models::*;
 use crate::util::function;
 use crate::{api::util::GameHistoryResponse, error::DieselError};
 use anyhow::{Ok, Result};
+use chrono::{Duration, Local, NaiveDateTime};
 use diesel::dsl::exists;
+use diesel::upsert::excluded;
 use diesel::{prelude::*, select};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -77,6 +82,36 @@ pub struct BuildingTypeResponse {
     pub capacity: i32,
     pub block_id: i32,
     pub hp: i32,
+    pub production_rate: i32,
+}
+
+#[derive(Serialize)]
+pub struct BuildingArtifacts {
+    pub map_space_id: i32,
+    pub artifacts: i32,
+}
+
+#[derive(Serialize)]
+pub struct ArtifactDistributionResponse {
+    pub bank_map_space_id: i32,
+    pub artifacts_in_bank: i32,
+    pub buildings: Vec<BuildingArtifacts>,
+}
+
+#[derive(Serialize)]
+pub struct ProductionResponse {
+    pub map_space_id: i32,
+    pub production_rate: i32,
+    pub capacity: i32,
+    pub uncollected: i32,
+}
+
+#[derive(Serialize)]
+pub struct CollectProductionResponse {
+    pub collected: i32,
+    pub bank_map_space_id: i32,
+    pub artifacts_in_bank: i32,
+    pub production: Vec<ProductionResponse>,
 }
 
 #[derive(Serialize)]
@@ -161,46 +196,217 @@ pub fn defender_exists(defender: i32, conn: &mut PgConnection) -> Result<bool> {
 
 pub fn transfer_artifacts_building(
     conn: &mut PgConnection,
+    user_id: &i32,
     building_map_space_id: &i32,
     bank_map_space_id: &i32,
-    new_building_artifact_count: &i32,
-    new_bank_artifact_count: &i32,
+    artifacts_differ: &i32,
 ) -> Result<()> {
     use crate::schema::artifact;
 
-    diesel::update(artifact::table.filter(artifact::map_space_id.eq(bank_map_space_id)))
-        .set(artifact::count.eq(new_bank_artifact_count))
-        .execute(conn)
-        .map_err(|err| DieselError {
-            table: "map_spaces",
-            function: function!(),
-            error: err,
-        })
-        .unwrap();
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, *user_id)?;
+        move_artifacts(
+            ArtifactAccount::Building {
+                user_id: *user_id,
+                map_space_id: *bank_map_space_id,
+            },
+            ArtifactAccount::Building {
+                user_id: *user_id,
+                map_space_id: *building_map_space_id,
+            },
+            *artifacts_differ,
+            ArtifactLedgerReason::Transfer,
+            None,
+            conn,
+        )?;
 
-    if *new_building_artifact_count == 0 {
         diesel::delete(
-            artifact::dsl::artifact.filter(artifact::dsl::map_space_id.eq(building_map_space_id)),
+            artifact::table
+                .filter(artifact::map_space_id.eq(building_map_space_id))
+                .filter(artifact::count.eq(0)),
         )
         .execute(conn)
         .map_err(|err| DieselError {
-            table: "shortest_path",
+            table: "artifact",
             function: function!(),
             error: err,
         })?;
         Ok(())
-    } else {
-        diesel::update(artifact::table.filter(artifact::map_space_id.eq(building_map_space_id)))
-            .set(artifact::count.eq(new_building_artifact_count))
-            .execute(conn)
+    })
+}
+
+// Serialises artifact changes of a user, held until the surrounding transaction ends
+pub fn lock_user_artifacts(conn: &mut PgConnection, user_id: i32) -> Result<()> {
+    use crate::schema::user;
+
+    user::table
+        .find(user_id)
+        .select(user::id)
+        .for_update()
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
+// Moves the artifacts of the user's buildings to the given counts in one go. Buildings left out keep
+// their artifacts, and the bank takes whatever is left over unless it is given a count of its own
+pub fn distribute_artifacts(
+    conn: &mut PgConnection,
+    user_id: i32,
+    distribution: &[ArtifactDistributionEntry],
+) -> Result<ArtifactDistributionResponse> {
+    use crate::schema::{artifact, block_type, building_type, map_spaces};
+
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, user_id)?;
+        let map_id = fetch_map_layout(conn, &user_id)?.id;
+
+        // map_space_id -> (capacity, artifacts, is bank)
+        let buildings: HashMap<i32, (i32, i32, bool)> = map_spaces::table
+            .inner_join(block_type::table.inner_join(building_type::table))
+            .left_join(artifact::table)
+            .filter(map_spaces::map_id.eq(map_id))
+            .filter(block_type::category.eq(BlockCategory::Building))
+            .filter(building_type::id.ne(ROAD_ID))
+            .select((
+                map_spaces::id,
+                building_type::name,
+                building_type::capacity,
+                artifact::count.nullable(),
+            ))
+            .load::<(i32, String, i32, Option<i32>)>(conn)
             .map_err(|err| DieselError {
                 table: "map_spaces",
                 function: function!(),
                 error: err,
+            })?
+            .into_iter()
+            .map(|(map_space_id, name, capacity, count)| {
+                let is_bank = name == BANK_BUILDING_NAME;
+                (map_space_id, (capacity, count.unwrap_or(0), is_bank))
             })
-            .unwrap();
-        Ok(())
-    }
+            .collect();
+
+        let bank_map_space_id = buildings
+            .iter()
+            .find(|(_, (_, _, is_bank))| *is_bank)
+            .map(|(map_space_id, _)| *map_space_id)
+            .ok_or(ArtifactDistributionError::MissingBank)?;
+
+        let mut targets: HashMap<i32, i32> = buildings
+            .iter()
+            .map(|(map_space_id, (_, count, _))| (*map_space_id, *count))
+            .collect();
+        let mut given: Vec<i32> = Vec::new();
+        for entry in distribution {
+            let (capacity, _, _) = buildings.get(&entry.map_space_id).ok_or(
+                ArtifactDistributionError::InvalidBuilding(entry.map_space_id),
+            )?;
+            if given.contains(&entry.map_space_id) {
+                return Err(
+                    ArtifactDistributionError::DuplicateBuilding(entry.map_space_id).into(),
+                );
+            }
+            if entry.artifacts < 0 {
+                return Err(ArtifactDistributionError::NegativeCount(entry.map_space_id).into());
+            }
+            if entry.artifacts > *capacity {
+                return Err(ArtifactDistributionError::CapacityExceeded(entry.map_space_id).into());
+            }
+            given.push(entry.map_space_id);
+            targets.insert(entry.map_space_id, entry.artifacts);
+        }
+
+        let total: i64 = buildings.values().map(|(_, count, _)| *count as i64).sum();
+        if !given.contains(&bank_map_space_id) {
+            let in_buildings: i64 = targets
+                .iter()
+                .filter(|(map_space_id, _)| **map_space_id != bank_map_space_id)
+                .map(|(_, artifacts)| *artifacts as i64)
+                .sum();
+            let bank_capacity = buildings[&bank_map_space_id].0 as i64;
+            if total - in_buildings < 0 {
+                return Err(ArtifactDistributionError::TotalMismatch.into());
+            }
+            if total - in_buildings > bank_capacity {
+                return Err(ArtifactDistributionError::CapacityExceeded(bank_map_space_id).into());
+            }
+            targets.insert(bank_map_space_id, (total - in_buildings) as i32);
+        }
+        if targets
+            .values()
+            .map(|artifacts| *artifacts as i64)
+            .sum::<i64>()
+            != total
+        {
+            return Err(ArtifactDistributionError::TotalMismatch.into());
+        }
+
+        //empty buildings into the bank first so the bank always has enough to hand out
+        let bank = ArtifactAccount::Building {
+            user_id,
+            map_space_id: bank_map_space_id,
+        };
+        let mut changes: Vec<(i32, i32)> = targets
+            .iter()
+            .filter(|(map_space_id, _)| **map_space_id != bank_map_space_id)
+            .map(|(map_space_id, artifacts)| {
+                (*map_space_id, *artifacts - buildings[map_space_id].1)
+            })
+            .filter(|(_, change)| *change != 0)
+            .collect();
+        changes.sort_by_key(|(_, change)| *change);
+        for (map_space_id, change) in &changes {
+            move_artifacts(
+                bank,
+                ArtifactAccount::Building {
+                    user_id,
+                    map_space_id: *map_space_id,
+                },
+                *change,
+                ArtifactLedgerReason::Transfer,
+                None,
+                conn,
+            )?;
+        }
+
+        let emptied: Vec<i32> = changes
+            .iter()
+            .filter(|(map_space_id, _)| targets[map_space_id] == 0)
+            .map(|(map_space_id, _)| *map_space_id)
+            .collect();
+        diesel::delete(
+            artifact::table
+                .filter(artifact::map_space_id.eq_any(emptied))
+                .filter(artifact::count.eq(0)),
+        )
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "artifact",
+            function: function!(),
+            error: err,
+        })?;
+
+        let mut buildings: Vec<BuildingArtifacts> = targets
+            .iter()
+            .filter(|(map_space_id, _)| **map_space_id != bank_map_space_id)
+            .map(|(map_space_id, artifacts)| BuildingArtifacts {
+                map_space_id: *map_space_id,
+                artifacts: *artifacts,
+            })
+            .collect();
+        buildings.sort_by_key(|building| building.map_space_id);
+
+        Ok(ArtifactDistributionResponse {
+            bank_map_space_id,
+            artifacts_in_bank: targets[&bank_map_space_id],
+            buildings,
+        })
+    })
 }
 
 pub fn create_artifact_record(
@@ -335,6 +541,238 @@ pub fn get_building_capacity(conn: &mut PgConnection, given_map_space_id: &i32)
     Ok(building_capacity)
 }
 
+// Uncollected production of a building as of now, capped at its capacity. updated_at only moves
+// forward by the time it took to produce whole artifacts so that no partial artifact is lost
+fn accrue(
+    uncollected: i32,
+    updated_at: NaiveDateTime,
+    production_rate: i32,
+    capacity: i32,
+    now: NaiveDateTime,
+) -> (i32, NaiveDateTime) {
+    if uncollected >= capacity {
+        return (uncollected.min(capacity.max(0)), now);
+    }
+    let elapsed = (now - updated_at).num_seconds().max(0);
+    let produced = elapsed * production_rate as i64 / PRODUCTION_INTERVAL_SECONDS;
+    if uncollected as i64 + produced >= capacity as i64 {
+        return (capacity, now);
+    }
+    let production_time = produced * PRODUCTION_INTERVAL_SECONDS / production_rate as i64;
+    (
+        uncollected + produced as i32,
+        updated_at + Duration::seconds(production_time),
+    )
+}
+
+// Brings the production of every production building on the map up to date
+pub fn accrue_production(conn: &mut PgConnection, map_id: i32) -> Result<Vec<ProductionResponse>> {
+    use crate::schema::{block_type, building_type, map_spaces, production};
+
+    conn.transaction(|conn| {
+        //the rows stay locked until they are written back, so a concurrent loot isn't overwritten
+        production::table
+            .filter(
+                production::map_space_id.eq_any(
+                    map_spaces::table
+                        .filter(map_spaces::map_id.eq(map_id))
+                        .select(map_spaces::id),
+                ),
+            )
+            .select(production::map_space_id)
+            .for_update()
+            .load::<i32>(conn)
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?;
+
+        let buildings = map_spaces::table
+            .inner_join(block_type::table.inner_join(building_type::table))
+            .left_join(production::table)
+            .filter(map_spaces::map_id.eq(map_id))
+            .filter(building_type::production_rate.gt(0))
+            .select((
+                map_spaces::id,
+                building_type::production_rate,
+                building_type::capacity,
+                production::uncollected.nullable(),
+                production::updated_at.nullable(),
+            ))
+            .load::<(i32, i32, i32, Option<i32>, Option<NaiveDateTime>)>(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?;
+        if buildings.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Local::now().naive_local();
+        let mut productions = Vec::new();
+        let mut entries = Vec::new();
+        for (map_space_id, production_rate, capacity, uncollected, updated_at) in buildings {
+            let (uncollected, updated_at) = accrue(
+                uncollected.unwrap_or(0),
+                updated_at.unwrap_or(now),
+                production_rate,
+                capacity,
+                now,
+            );
+            entries.push(NewProduction {
+                map_space_id,
+                uncollected,
+                updated_at,
+            });
+            productions.push(ProductionResponse {
+                map_space_id,
+                production_rate,
+                capacity,
+                uncollected,
+            });
+        }
+
+        diesel::insert_into(production::table)
+            .values(&entries)
+            .on_conflict(production::map_space_id)
+            .do_update()
+            .set((
+                production::uncollected.eq(excluded(production::uncollected)),
+                production::updated_at.eq(excluded(production::updated_at)),
+            ))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?;
+        Ok(productions)
+    })
+}
+
+pub fn fetch_production(conn: &mut PgConnection, user_id: i32) -> Result<Vec<ProductionResponse>> {
+    let map_id = fetch_map_layout(conn, &user_id)?.id;
+    accrue_production(conn, map_id)
+}
+
+// Moves the uncollected production of the user's buildings into their bank, as much as it has room for
+pub fn collect_production(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<CollectProductionResponse> {
+    use crate::schema::production;
+
+    conn.transaction(|conn| {
+        //concurrent collections wait here so that the same production can't be collected twice
+        lock_user_artifacts(conn, user_id)?;
+        let map_id = fetch_map_layout(conn, &user_id)?.id;
+        let bank_block_type_id = get_block_id_of_bank(conn, &user_id)?;
+        let bank_map_space_id = get_bank_map_space_id(conn, &map_id, &bank_block_type_id)?;
+        let bank_capacity = get_building_capacity(conn, &bank_map_space_id)?;
+        let mut artifacts_in_bank =
+            get_building_artifact_count(conn, &map_id, &bank_map_space_id)?.max(0);
+
+        let mut productions = accrue_production(conn, map_id)?;
+        let mut collected = 0;
+        for building in productions.iter_mut() {
+            let amount = building.uncollected.min(bank_capacity - artifacts_in_bank);
+            if amount <= 0 {
+                continue;
+            }
+            let updated = diesel::update(production::table.find(building.map_space_id))
+                .filter(production::uncollected.ge(amount))
+                .set(production::uncollected.eq(production::uncollected - amount))
+                .execute(conn)
+                .map_err(|err| DieselError {
+                    table: "production",
+                    function: function!(),
+                    error: err,
+                })?;
+            if updated == 0 {
+                return Err(anyhow::anyhow!(
+                    "Production of building:{} was taken while collecting",
+                    building.map_space_id
+                ));
+            }
+            move_artifacts(
+                ArtifactAccount::System,
+                ArtifactAccount::Building {
+                    user_id,
+                    map_space_id: bank_map_space_id,
+                },
+                amount,
+                ArtifactLedgerReason::Production,
+                Some(building.map_space_id),
+                conn,
+            )?;
+            building.uncollected -= amount;
+            artifacts_in_bank += amount;
+            collected += amount;
+        }
+
+        Ok(CollectProductionResponse {
+            collected,
+            bank_map_space_id,
+            artifacts_in_bank,
+            production: productions,
+        })
+    })
+}
+
+// Takes up to amount artifacts of a building's uncollected production, minting them into the
+// attacker's bank. Returns the number of artifacts taken
+pub fn loot_production(
+    conn: &mut PgConnection,
+    map_space_id: i32,
+    amount: i32,
+    attacker: ArtifactAccount,
+    game_id: i32,
+) -> Result<i32> {
+    use crate::schema::production;
+
+    if amount <= 0 {
+        return Ok(0);
+    }
+    conn.transaction(|conn| {
+        let uncollected = production::table
+            .find(map_space_id)
+            .select(production::uncollected)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?
+            .unwrap_or(0);
+        let taken = amount.min(uncollected);
+        if taken <= 0 {
+            return Ok(0);
+        }
+
+        diesel::update(production::table.find(map_space_id))
+            .set(production::uncollected.eq(production::uncollected - taken))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?;
+        move_artifacts(
+            ArtifactAccount::System,
+            attacker,
+            taken,
+            ArtifactLedgerReason::Loot,
+            Some(game_id),
+            conn,
+        )?;
+        Ok(taken)
+    })
+}
+
 pub fn fetch_map_layout(conn: &mut PgConnection, player: &i32) -> Result<MapLayout> {
     use crate::schema::map_layout;
 
@@ -592,79 +1030,192 @@ pub fn put_base_details(
     conn: &mut PgConnection,
 ) -> Result<()> {
     use crate::schema::artifact;
+    use crate::schema::map_layout;
     use crate::schema::map_spaces::dsl::*;
+    use crate::schema::production;
+    use crate::schema::user;
 
-    diesel::delete(artifact::table)
-        .filter(artifact::map_space_id.eq_any(map_spaces.filter(map_id.eq(map.id)).select(id)))
-        .execute(conn)
-        .map_err(|err| DieselError {
-            table: "artifact",
-            function: function!(),
-            error: err,
-        })?;
+    //the old layout's artifacts are moved onto the new one as a single change
+    conn.transaction(|conn| {
+        // Bumping the version invalidates anything cached for the old layout
+        diesel::update(map_layout::table.find(map.id))
+            .set(map_layout::version.eq(map_layout::version + 1))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "map_layout",
+                function: function!(),
+                error: err,
+            })?;
 
-    diesel::delete(map_spaces)
-        .filter(map_id.eq(map.id))
-        .execute(conn)
-        .map_err(|err| DieselError {
-            table: "map_spaces",
-            function: function!(),
-            error: err,
-        })?;
+        //uncollected production stays with buildings that are left in place
+        let productions: HashMap<(i32, i32, i32), Production> = production::table
+            .inner_join(map_spaces)
+            .filter(map_id.eq(map.id))
+            .select((
+                (x_coordinate, y_coordinate, block_type_id),
+                production::all_columns,
+            ))
+            .load::<((i32, i32, i32), Production)>(conn)
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?
+            .into_iter()
+            .collect();
 
-    let m: Vec<NewMapSpaces> = maps
-        .iter()
-        .map(|e| NewMapSpaces {
-            map_id: map.id,
-            x_coordinate: e.x_coordinate,
-            y_coordinate: e.y_coordinate,
-            block_type_id: e.block_type_id,
-        })
-        .collect();
+        //artifacts are only moved around the base, never created or destroyed by saving it
+        lock_user_artifacts(conn, map.player)?;
+        let user_artifacts = user::table
+            .find(map.player)
+            .select(user::artifacts)
+            .first::<i32>(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+        let total_artifacts: i32 = maps.iter().map(|e| e.artifacts.max(0)).sum();
+        if total_artifacts != user_artifacts {
+            return Err(BaseInvalidError::InvalidArtifactCount.into());
+        }
+
+        let old_artifacts: Vec<(i32, i32)> = artifact::table
+            .filter(artifact::map_space_id.eq_any(map_spaces.filter(map_id.eq(map.id)).select(id)))
+            .filter(artifact::count.gt(0))
+            .select((artifact::map_space_id, artifact::count))
+            .load::<(i32, i32)>(conn)
+            .map_err(|err| DieselError {
+                table: "artifact",
+                function: function!(),
+                error: err,
+            })?;
+        for (old_map_space_id, count) in old_artifacts {
+            add_ledger_entry(
+                ArtifactAccount::Building {
+                    user_id: map.player,
+                    map_space_id: old_map_space_id,
+                },
+                ArtifactAccount::User(map.player),
+                count,
+                ArtifactLedgerReason::Transfer,
+                Some(map.id),
+                conn,
+            )?;
+        }
+
+        diesel::delete(artifact::table)
+            .filter(artifact::map_space_id.eq_any(map_spaces.filter(map_id.eq(map.id)).select(id)))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "artifact",
+                function: function!(),
+                error: err,
+            })?;
 
-    let result: Vec<MapSpaces> = diesel::insert_into(map_spaces)
-        .values(m)
-        .on_conflict_do_nothing()
-        .get_results(conn)
-        .map_err(|err| DieselError {
-            table: "map_spaces",
-            function: function!(),
-            error: err,
-        })?;
+        diesel::delete(map_spaces)
+            .filter(map_id.eq(map.id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?;
 
-    let mut map_space_map: HashMap<(i32, i32), i32> = HashMap::new();
-    for map_space in result {
-        map_space_map.insert(
-            (map_space.x_coordinate, map_space.y_coordinate),
-            map_space.id,
-        );
-    }
+        let m: Vec<NewMapSpaces> = maps
+            .iter()
+            .map(|e| NewMapSpaces {
+                map_id: map.id,
+                x_coordinate: e.x_coordinate,
+                y_coordinate: e.y_coordinate,
+                block_type_id: e.block_type_id,
+            })
+            .collect();
 
-    let artifact_entries: Vec<NewArtifact> = maps
-        .iter()
-        .filter_map(|e| {
-            if e.artifacts > 0 {
-                Some(NewArtifact {
-                    map_space_id: map_space_map[&(e.x_coordinate, e.y_coordinate)],
-                    count: e.artifacts,
-                })
-            } else {
-                None
-            }
-        })
-        .collect();
+        let result: Vec<MapSpaces> = diesel::insert_into(map_spaces)
+            .values(m)
+            .on_conflict_do_nothing()
+            .get_results(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?;
 
-    diesel::insert_into(artifact::table)
-        .values(artifact_entries)
-        .on_conflict_do_nothing()
-        .execute(conn)
-        .map_err(|err| DieselError {
-            table: "artifact",
-            function: function!(),
-            error: err,
-        })?;
+        let production_entries: Vec<NewProduction> = result
+            .iter()
+            .filter_map(|map_space| {
+                productions
+                    .get(&(
+                        map_space.x_coordinate,
+                        map_space.y_coordinate,
+                        map_space.block_type_id,
+                    ))
+                    .map(|production| NewProduction {
+                        map_space_id: map_space.id,
+                        uncollected: production.uncollected,
+                        updated_at: production.updated_at,
+                    })
+            })
+            .collect();
 
-    Ok(())
+        diesel::insert_into(production::table)
+            .values(production_entries)
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "production",
+                function: function!(),
+                error: err,
+            })?;
+
+        let mut map_space_map: HashMap<(i32, i32), i32> = HashMap::new();
+        for map_space in result {
+            map_space_map.insert(
+                (map_space.x_coordinate, map_space.y_coordinate),
+                map_space.id,
+            );
+        }
+
+        let artifact_entries: Vec<NewArtifact> = maps
+            .iter()
+            .filter_map(|e| {
+                if e.artifacts > 0 {
+                    Some(NewArtifact {
+                        map_space_id: map_space_map[&(e.x_coordinate, e.y_coordinate)],
+                        count: e.artifacts,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for entry in artifact_entries.iter() {
+            add_ledger_entry(
+                ArtifactAccount::User(map.player),
+                ArtifactAccount::Building {
+                    user_id: map.player,
+                    map_space_id: entry.map_space_id,
+                },
+                entry.count,
+                ArtifactLedgerReason::Transfer,
+                Some(map.id),
+                conn,
+            )?;
+        }
+
+        diesel::insert_into(artifact::table)
+            .values(artifact_entries)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "artifact",
+                function: function!(),
+                error: err,
+            })?;
+
+        Ok(())
+    })
 }
 
 pub fn get_level_constraints(
@@ -701,6 +1252,7 @@ pub fn fetch_defense_historyboard(
 
     let joined_table = game::table
         .filter(game::defend_id.eq(user_id))
+        .filter(game::is_practice.eq(false))
         .inner_join(map_layout::table.inner_join(levels_fixture::table))
         .inner_join(user::table.on(game::attack_id.eq(user::id)));
 
@@ -733,9 +1285,11 @@ pub fn fetch_defense_historyboard(
                 damage_percent: game.damage_done,
                 artifacts_taken: -game.artifacts_collected,
                 trophies_taken: game.defend_score,
+                stars: game.stars,
                 match_id: game.id,
                 replay_availability: is_replay_available,
                 avatar_id: user.avatar_id,
+                is_revenged: game.is_revenged,
             })
         })
         .collect();
@@ -746,7 +1300,9 @@ pub fn fetch_defense_historyboard(
 pub fn fetch_top_defenses(user_id: i32, conn: &mut PgConnection) -> Result<GameHistoryResponse> {
     use crate::schema::{game, levels_fixture, map_layout};
 
-    let joined_table = game::table.inner_join(map_layout::table.inner_join(levels_fixture::table));
+    let joined_table = game::table
+        .filter(game::is_practice.eq(false))
+        .inner_join(map_layout::table.inner_join(levels_fixture::table));
     let games_result: Result<Vec<GameHistoryEntry>> = joined_table
         .order_by(game::defend_score.desc())
         .limit(10)
@@ -877,6 +1433,7 @@ pub fn fetch_building_blocks(
             capacity: building_type.capacity,
             block_id: block_type.id,
             hp: building_type.hp,
+            production_rate: building_type.production_rate,
         })
         .collect();
     Ok(buildings)
@@ -1087,6 +1644,16 @@ pub fn add_user_default_base(
                 error: err,
             })?;
 
+        //the starting artifacts are already laid out in the copied base
+        add_ledger_entry(
+            ArtifactAccount::System,
+            ArtifactAccount::User(user.id),
+            INITIAL_ARTIFACTS,
+            ArtifactLedgerReason::Initial,
+            None,
+            conn,
+        )?;
+
         Ok(user)
     })
 }
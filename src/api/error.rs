@@ -82,6 +82,126 @@ impl ResponseError for BaseInvalidError {
     }
 }
 
+#[derive(Debug, Display, Error)]
+pub enum ArtifactDistributionError {
+    MissingBank,
+    InvalidBuilding(i32),
+    DuplicateBuilding(i32),
+    NegativeCount(i32),
+    CapacityExceeded(i32),
+    TotalMismatch,
+}
+
+impl ResponseError for ArtifactDistributionError {
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let response_body = match self {
+            ArtifactDistributionError::MissingBank => "Base has no bank".to_string(),
+            ArtifactDistributionError::InvalidBuilding(map_space_id) => {
+                format!("Map space {map_space_id} is not a building of your base")
+            }
+            ArtifactDistributionError::DuplicateBuilding(map_space_id) => {
+                format!("Map space {map_space_id} is given more than once")
+            }
+            ArtifactDistributionError::NegativeCount(map_space_id) => {
+                format!("Artifacts of map space {map_space_id} cannot be negative")
+            }
+            ArtifactDistributionError::CapacityExceeded(map_space_id) => {
+                format!("Building capacity of map space {map_space_id} not sufficient")
+            }
+            ArtifactDistributionError::TotalMismatch => {
+                "Distribution does not add up to the artifacts in your base".to_string()
+            }
+        };
+        ErrorBadRequest(response_body).into()
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum UpgradeError {
+    NotOwned(String),
+    MaxLevel(String),
+    NotEnoughArtifacts,
+    NotEnoughArtifactsInBank,
+    AlreadyUpgrading,
+    BuildersBusy,
+    UpgradeNotFound(i32),
+    AlreadyComplete(i32),
+}
+
+impl ResponseError for UpgradeError {
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let response_body = match self {
+            UpgradeError::NotOwned(kind) => format!("You do not have this {kind}"),
+            UpgradeError::MaxLevel(kind) => format!("The {kind} is at max level"),
+            UpgradeError::NotEnoughArtifacts => "Not enough artifacts".to_string(),
+            UpgradeError::NotEnoughArtifactsInBank => "Not enough artifacts in bank".to_string(),
+            UpgradeError::AlreadyUpgrading => "Item is already being upgraded".to_string(),
+            UpgradeError::BuildersBusy => "All builders are busy".to_string(),
+            UpgradeError::UpgradeNotFound(upgrade_id) => format!("Upgrade {upgrade_id} not found"),
+            UpgradeError::AlreadyComplete(upgrade_id) => {
+                format!("Upgrade {upgrade_id} is already complete")
+            }
+        };
+        ErrorBadRequest(response_body).into()
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum ShopError {
+    UnlockableNotFound(i32),
+    AlreadyUnlocked,
+    NotEnoughTrophies(i32),
+    BankLevelTooLow(i32),
+    NotEnoughArtifactsInBank,
+}
+
+impl ResponseError for ShopError {
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let response_body = match self {
+            ShopError::UnlockableNotFound(unlockable_id) => {
+                format!("Item {unlockable_id} is not in the shop")
+            }
+            ShopError::AlreadyUnlocked => "You have already unlocked this item".to_string(),
+            ShopError::NotEnoughTrophies(min_trophies) => {
+                format!("You need {min_trophies} trophies to unlock this item")
+            }
+            ShopError::BankLevelTooLow(min_bank_level) => {
+                format!("Your bank needs to be at level {min_bank_level} to unlock this item")
+            }
+            ShopError::NotEnoughArtifactsInBank => "Not enough artifacts in bank".to_string(),
+        };
+        ErrorBadRequest(response_body).into()
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum RefundError {
+    NotOwned(String),
+    NotPlaced(String),
+    NotSellable(String),
+    LowestLevel(String),
+    Upgrading,
+    ArtifactsDontFit,
+    BankFull,
+}
+
+impl ResponseError for RefundError {
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let response_body = match self {
+            RefundError::NotOwned(kind) => format!("You do not have this {kind}"),
+            RefundError::NotPlaced(kind) => format!("This {kind} is not placed in your base"),
+            RefundError::NotSellable(name) => format!("{name} cannot be sold"),
+            RefundError::LowestLevel(kind) => format!("The {kind} is at its lowest level"),
+            RefundError::Upgrading => "Item is being upgraded".to_string(),
+            RefundError::ArtifactsDontFit => {
+                "Move the artifacts out of the building before doing this".to_string()
+            }
+            RefundError::BankFull => "Not enough room in the bank for the refund".to_string(),
+        };
+        ErrorBadRequest(response_body).into()
+    }
+}
+
 pub fn handle_error(err: Box<dyn std::error::Error>) -> actix_web::Error {
     log::error!("{}", err);
     ErrorInternalServerError("Internal Server Error")
@@ -1,4 +1,5 @@
-use super::{auth::session::AuthUser, error, PgPool};
+use super::league::util::fetch_league;
+use super::{auth::session::AuthUser, error, PgPool, RedisPool};
 use actix_web::{error::ErrorBadRequest, web, Responder, Result};
 use util::LeaderboardQuery;
 
@@ -13,15 +14,25 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
 async fn list_leaderboard(
     query: web::Query<LeaderboardQuery>,
     pool: web::Data<PgPool>,
+    redis_pool: web::Data<RedisPool>,
 ) -> Result<impl Responder> {
     let page = query.page.unwrap_or(1);
     let limit = query.limit.unwrap_or(20);
     if page <= 0 || limit <= 0 {
         return Err(ErrorBadRequest("Invalid query params"));
     }
+    let league_id = query.league_id;
     let response = web::block(move || {
         let mut conn = pool.get()?;
-        util::get_leaderboard(page, limit, &mut conn)
+        let mut redis_conn = redis_pool.get()?;
+        let league = match league_id {
+            Some(league_id) => Some(
+                fetch_league(league_id, &mut conn)?
+                    .ok_or_else(|| anyhow::anyhow!("League:{} not found", league_id))?,
+            ),
+            None => None,
+        };
+        util::get_leaderboard(page, limit, league.as_ref(), &mut conn, &mut redis_conn)
     })
     .await?
     .map_err(|err| error::handle_error(err.into()))?;
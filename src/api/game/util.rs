@@ -1,4 +1,9 @@
+use crate::api::leaderboard::util::{
+    count_players_in_range, fetch_leaderboard_entries, get_players_in_range,
+};
+use crate::api::league::util::LeagueResponse;
 use crate::api::util::can_show_replay;
+use crate::api::RedisConn;
 use crate::error::DieselError;
 use crate::models::{Game, LevelsFixture, MapLayout, SimulationLog};
 use crate::util::function;
@@ -19,6 +24,7 @@ pub struct UserDetail {
 pub struct LeaderboardQuery {
     pub page: Option<i64>,
     pub limit: Option<i64>,
+    pub league_id: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -30,6 +36,7 @@ pub struct LeaderboardResponse {
 #[derive(Queryable, Deserialize, Serialize)]
 pub struct LeaderboardEntry {
     pub user_id: i32,
+    pub rank: i64,
     pub name: String,
     pub trophies: i32,
     pub artifacts: i32,
@@ -41,56 +48,21 @@ pub struct LeaderboardEntry {
 pub fn get_leaderboard(
     page: i64,
     limit: i64,
+    league: Option<&LeagueResponse>,
     conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
 ) -> Result<LeaderboardResponse> {
-    use crate::schema::user;
+    let (min_trophies, max_trophies) = match league {
+        Some(league) => (league.min_trophies, league.max_trophies.unwrap_or(i32::MAX)),
+        None => (i32::MIN, i32::MAX),
+    };
 
-    let total_entries: i64 = user::table
-        .count()
-        .get_result(conn)
-        .map_err(|err| DieselError {
-            table: "user",
-            function: function!(),
-            error: err,
-        })?;
+    let total_entries = count_players_in_range(min_trophies, max_trophies, redis_conn)?;
     let off_set: i64 = (page - 1) * limit;
     let last_page: i64 = (total_entries as f64 / limit as f64).ceil() as i64;
 
-    let leaderboard_entries = user::table
-        .filter(user::is_pragyan.eq(false))
-        .select((
-            user::id,
-            user::username,
-            user::trophies,
-            user::artifacts,
-            user::attacks_won,
-            user::defenses_won,
-            user::avatar_id,
-        ))
-        .order_by(user::trophies.desc())
-        .offset(off_set)
-        .limit(limit)
-        .load::<(i32, String, i32, i32, i32, i32, i32)>(conn)
-        .map_err(|err| DieselError {
-            table: "user_join_map_layout",
-            function: function!(),
-            error: err,
-        })?
-        .into_iter()
-        .map(
-            |(id, name, trophies, artifacts, attacks_won, defenses_won, avatar_id)| {
-                LeaderboardEntry {
-                    user_id: id,
-                    name,
-                    trophies,
-                    artifacts,
-                    attacks_won,
-                    defenses_won,
-                    avatar_url: avatar_id,
-                }
-            },
-        )
-        .collect();
+    let players = get_players_in_range(min_trophies, max_trophies, off_set, limit, redis_conn)?;
+    let leaderboard_entries = fetch_leaderboard_entries(&players, off_set + 1, conn)?;
 
     Ok(LeaderboardResponse {
         leaderboard_entries,
@@ -0,0 +1,343 @@
+use super::upgrade::{fetch_bank, ItemLevel};
+use super::util::get_user_map_id;
+use crate::api::defense::util::lock_user_artifacts;
+use crate::api::error::UpgradeError;
+use crate::api::ledger::util::{move_artifacts, ArtifactAccount};
+use crate::api::util::get_current_levels_fixture;
+use crate::error::DieselError;
+use crate::models::{ArtifactLedgerReason, ItemCategory, NewUpgradeQueueEntry, UpgradeQueueEntry};
+use crate::schema::{available_blocks, map_spaces, upgrade_queue, user};
+use crate::util::function;
+use anyhow::{Ok, Result};
+use chrono::{Duration, Local, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct UpgradeQueueResponse {
+    pub builders: i32,
+    pub upgrades: Vec<UpgradeQueueEntry>,
+}
+
+// An upgrade to level n takes n times the minutes per level of the current level fixture
+pub fn upgrade_duration(conn: &mut PgConnection, next_level: i32) -> Result<Duration> {
+    let minutes_per_level = get_current_levels_fixture(conn)?.upgrade_minutes_per_level;
+    Ok(Duration::minutes((minutes_per_level * next_level) as i64))
+}
+
+fn fetch_pending_upgrades(
+    player_id: i32,
+    conn: &mut PgConnection,
+) -> Result<Vec<UpgradeQueueEntry>> {
+    let upgrades = upgrade_queue::table
+        .filter(upgrade_queue::user_id.eq(player_id))
+        .filter(upgrade_queue::is_complete.eq(false))
+        .order_by(upgrade_queue::finishes_at.asc())
+        .load::<UpgradeQueueEntry>(conn)
+        .map_err(|err| DieselError {
+            table: "upgrade_queue",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(upgrades)
+}
+
+// Swaps one level of an item for another in the player's inventory, and in their base for blocks
+pub fn swap_item_level(
+    player_id: i32,
+    category: ItemCategory,
+    from_id: i32,
+    to_id: i32,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    let player_available_blocks =
+        available_blocks::table.filter(available_blocks::user_id.eq(player_id));
+
+    match category {
+        ItemCategory::Block => {
+            diesel::update(
+                player_available_blocks.filter(available_blocks::block_type_id.eq(from_id)),
+            )
+            .set(available_blocks::block_type_id.eq(to_id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "available_blocks",
+                function: function!(),
+                error: err,
+            })?;
+
+            let id_of_map = get_user_map_id(player_id, conn)?;
+            diesel::update(
+                map_spaces::table
+                    .filter(map_spaces::block_type_id.eq(from_id))
+                    .filter(map_spaces::map_id.eq(id_of_map)),
+            )
+            .set(map_spaces::block_type_id.eq(to_id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?;
+        }
+        ItemCategory::Attacker => {
+            diesel::update(
+                player_available_blocks.filter(available_blocks::attacker_type_id.eq(from_id)),
+            )
+            .set(available_blocks::attacker_type_id.eq(to_id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "available_blocks",
+                function: function!(),
+                error: err,
+            })?;
+        }
+        ItemCategory::Emp => {
+            diesel::update(
+                player_available_blocks.filter(available_blocks::emp_type_id.eq(from_id)),
+            )
+            .set(available_blocks::emp_type_id.eq(to_id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "available_blocks",
+                function: function!(),
+                error: err,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+// Applies a queued upgrade. Marking the entry complete first keeps an upgrade from being applied
+// twice, false if it already was
+fn apply_upgrade(entry: &UpgradeQueueEntry, conn: &mut PgConnection) -> Result<bool> {
+    let marked = diesel::update(
+        upgrade_queue::table
+            .find(entry.id)
+            .filter(upgrade_queue::is_complete.eq(false)),
+    )
+    .set(upgrade_queue::is_complete.eq(true))
+    .execute(conn)
+    .map_err(|err| DieselError {
+        table: "upgrade_queue",
+        function: function!(),
+        error: err,
+    })?;
+    if marked == 0 {
+        return Ok(false);
+    }
+
+    swap_item_level(
+        entry.user_id,
+        entry.category,
+        entry.from_id,
+        entry.to_id,
+        conn,
+    )?;
+    Ok(true)
+}
+
+// Pays the cost of the current level from the bank and hands the upgrade to a free builder.
+// Upgrades that take no time are applied straight away. The caller holds the user lock
+pub fn queue_upgrade(
+    player_id: i32,
+    category: ItemCategory,
+    current: &ItemLevel,
+    next: &ItemLevel,
+    bank_map_space_id: i32,
+    conn: &mut PgConnection,
+) -> Result<UpgradeQueueEntry> {
+    conn.transaction(|conn| {
+        let pending = fetch_pending_upgrades(player_id, conn)?;
+        if pending
+            .iter()
+            .any(|entry| entry.category == category && entry.from_id == current.id)
+        {
+            return Err(UpgradeError::AlreadyUpgrading.into());
+        }
+        let builders = user::table
+            .find(player_id)
+            .select(user::builders)
+            .first::<i32>(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+        if pending.len() as i32 >= builders {
+            return Err(UpgradeError::BuildersBusy.into());
+        }
+
+        //pay for the upgrade from the bank
+        move_artifacts(
+            ArtifactAccount::Building {
+                user_id: player_id,
+                map_space_id: bank_map_space_id,
+            },
+            ArtifactAccount::System,
+            current.cost,
+            ArtifactLedgerReason::Upgrade,
+            Some(next.id),
+            conn,
+        )?;
+
+        let started_at = Local::now().naive_local();
+        let finishes_at = started_at + upgrade_duration(conn, next.level)?;
+        let entry = diesel::insert_into(upgrade_queue::table)
+            .values(NewUpgradeQueueEntry {
+                user_id: player_id,
+                category,
+                from_id: current.id,
+                to_id: next.id,
+                cost: current.cost,
+                started_at,
+                finishes_at,
+                is_complete: false,
+            })
+            .get_result::<UpgradeQueueEntry>(conn)
+            .map_err(|err| DieselError {
+                table: "upgrade_queue",
+                function: function!(),
+                error: err,
+            })?;
+
+        if entry.finishes_at <= started_at {
+            apply_upgrade(&entry, conn)?;
+            return Ok(UpgradeQueueEntry {
+                is_complete: true,
+                ..entry
+            });
+        }
+        Ok(entry)
+    })
+}
+
+fn resolve_due_upgrades(
+    player_id: Option<i32>,
+    now: NaiveDateTime,
+    conn: &mut PgConnection,
+) -> Result<usize> {
+    let mut query = upgrade_queue::table
+        .filter(upgrade_queue::is_complete.eq(false))
+        .filter(upgrade_queue::finishes_at.le(now))
+        .order_by(upgrade_queue::finishes_at.asc())
+        .into_boxed();
+    if let Some(player_id) = player_id {
+        query = query.filter(upgrade_queue::user_id.eq(player_id));
+    }
+    let due = query
+        .load::<UpgradeQueueEntry>(conn)
+        .map_err(|err| DieselError {
+            table: "upgrade_queue",
+            function: function!(),
+            error: err,
+        })?;
+
+    let mut applied = 0;
+    for entry in &due {
+        if apply_upgrade(entry, conn)? {
+            applied += 1;
+        }
+    }
+    Ok(applied)
+}
+
+// Applies the player's upgrades that have finished, returns how many were applied
+pub fn resolve_upgrades(player_id: i32, conn: &mut PgConnection) -> Result<usize> {
+    let now = Local::now().naive_local();
+    conn.transaction(|conn| resolve_due_upgrades(Some(player_id), now, conn))
+}
+
+// Applies every finished upgrade, for the background worker
+pub fn resolve_all_upgrades(conn: &mut PgConnection) -> Result<usize> {
+    let now = Local::now().naive_local();
+    conn.transaction(|conn| resolve_due_upgrades(None, now, conn))
+}
+
+// Finishes a pending upgrade right away for speed_up_cost_per_minute artifacts per minute left
+pub fn speed_up_upgrade(
+    player_id: i32,
+    upgrade_id: i32,
+    conn: &mut PgConnection,
+) -> Result<UpgradeQueueEntry> {
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, player_id)?;
+
+        let entry = upgrade_queue::table
+            .find(upgrade_id)
+            .filter(upgrade_queue::user_id.eq(player_id))
+            .first::<UpgradeQueueEntry>(conn)
+            .optional()
+            .map_err(|err| DieselError {
+                table: "upgrade_queue",
+                function: function!(),
+                error: err,
+            })?
+            .ok_or(UpgradeError::UpgradeNotFound(upgrade_id))?;
+        if entry.is_complete {
+            return Err(UpgradeError::AlreadyComplete(upgrade_id).into());
+        }
+
+        let seconds_left = (entry.finishes_at - Local::now().naive_local()).num_seconds();
+        if seconds_left > 0 {
+            let minutes_left = (seconds_left + 59) / 60;
+            let cost_per_minute = get_current_levels_fixture(conn)?.speed_up_cost_per_minute;
+            let cost = (minutes_left * cost_per_minute as i64) as i32;
+            let (bank_map_space_id, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+            if artifacts_in_bank < cost {
+                return Err(UpgradeError::NotEnoughArtifactsInBank.into());
+            }
+            move_artifacts(
+                ArtifactAccount::Building {
+                    user_id: player_id,
+                    map_space_id: bank_map_space_id,
+                },
+                ArtifactAccount::System,
+                cost,
+                ArtifactLedgerReason::Upgrade,
+                Some(entry.to_id),
+                conn,
+            )?;
+        }
+
+        if !apply_upgrade(&entry, conn)? {
+            return Err(UpgradeError::AlreadyComplete(upgrade_id).into());
+        }
+        Ok(UpgradeQueueEntry {
+            is_complete: true,
+            ..entry
+        })
+    })
+}
+
+pub fn fetch_upgrade_queue(
+    player_id: i32,
+    conn: &mut PgConnection,
+) -> Result<UpgradeQueueResponse> {
+    let builders = user::table
+        .find(player_id)
+        .select(user::builders)
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    let upgrades = fetch_pending_upgrades(player_id, conn)?;
+    Ok(UpgradeQueueResponse { builders, upgrades })
+}
+
+// Block types of the player that can't defend while they are being upgraded. Empty when the
+// current level keeps upgrading blocks defending at their old level
+pub fn fetch_unavailable_block_types(player_id: i32, conn: &mut PgConnection) -> Result<Vec<i32>> {
+    if get_current_levels_fixture(conn)?.is_upgrading_defended {
+        return Ok(Vec::new());
+    }
+    let block_type_ids = fetch_pending_upgrades(player_id, conn)?
+        .into_iter()
+        .filter(|entry| entry.category == ItemCategory::Block)
+        .map(|entry| entry.from_id)
+        .collect();
+    Ok(block_type_ids)
+}
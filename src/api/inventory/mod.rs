@@ -1,4 +1,6 @@
-use self::util::{upgrade_attacker, upgrade_building, upgrade_defender, upgrade_emp, upgrade_mine};
+use self::builder::speed_up_upgrade;
+use self::upgrade::{upgrade_item, ItemKind};
+use super::error::{RefundError, ShopError, UpgradeError};
 use super::{
     attack::util::get_game_id_from_redis, auth::session::AuthUser, error, PgPool, RedisPool,
 };
@@ -8,11 +10,21 @@ use actix_web::{
     Responder, Result,
 };
 use serde::{Deserialize, Serialize};
+pub mod builder;
+pub mod refund;
+pub mod shop;
+pub mod upgrade;
 pub mod util;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/get").route(web::get().to(get_inventory)))
-        .service(web::resource("/upgrade").route(web::post().to(upgrade)));
+        .service(web::resource("/upgrade").route(web::post().to(upgrade)))
+        .service(web::resource("/upgrade/preview").route(web::get().to(preview_upgrade)))
+        .service(web::resource("/upgrade/speed_up").route(web::post().to(speed_up)))
+        .service(web::resource("/sell").route(web::post().to(sell)))
+        .service(web::resource("/downgrade").route(web::post().to(downgrade)))
+        .service(web::resource("/shop").route(web::get().to(get_shop)))
+        .service(web::resource("/shop/unlock").route(web::post().to(unlock)));
 }
 
 async fn get_inventory(user: AuthUser, pool: web::Data<PgPool>) -> Result<impl Responder> {
@@ -29,7 +41,7 @@ async fn get_inventory(user: AuthUser, pool: web::Data<PgPool>) -> Result<impl R
 
 #[derive(Deserialize, Serialize)]
 struct UpgradeStruct {
-    pub item_type: String,
+    pub item_type: ItemKind,
     pub item_id: i32,
 }
 
@@ -40,8 +52,7 @@ async fn upgrade(
     req: Json<UpgradeStruct>,
 ) -> Result<impl Responder> {
     let user_id = user.0;
-    let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
-    let item_type = &req.item_type;
+    let item_type = req.item_type;
     let item_id = req.item_id;
 
     let mut redis_conn = redis_pool
@@ -52,22 +63,189 @@ async fn upgrade(
         return Err(ErrorBadRequest("You are under attack. Cannot upgrade now"));
     }
 
-    let mut map_space_id_if_valid = 0;
-
-    match item_type.as_str() {
-        "attacker" => upgrade_attacker(user_id, &mut conn, item_id)
-            .map_err(|err| ErrorBadRequest(err.to_string()))?,
-        "building" => {
-            map_space_id_if_valid = upgrade_building(user_id, &mut conn, item_id)
-                .map_err(|err| ErrorBadRequest(err.to_string()))?;
-        }
-        "defender" => upgrade_defender(user_id, &mut conn, item_id)
-            .map_err(|err| ErrorBadRequest(err.to_string()))?,
-        "emp" => upgrade_emp(user_id, &mut conn, item_id)
-            .map_err(|err| ErrorBadRequest(err.to_string()))?,
-        "mine" => upgrade_mine(user_id, &mut conn, item_id)
-            .map_err(|err| ErrorBadRequest(err.to_string()))?,
-        _ => return Err(ErrorBadRequest("Invalid item type")),
+    let upgrade = web::block(move || {
+        let mut conn = pool.get()?;
+        upgrade_item(user_id, item_type, item_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<UpgradeError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(upgrade))
+}
+
+async fn preview_upgrade(
+    pool: web::Data<PgPool>,
+    user: AuthUser,
+    query: web::Query<UpgradeStruct>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let item_type = query.item_type;
+    let item_id = query.item_id;
+
+    let preview = web::block(move || {
+        let mut conn = pool.get()?;
+        upgrade::preview_upgrade(user_id, item_type, item_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<UpgradeError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(preview))
+}
+
+#[derive(Deserialize, Serialize)]
+struct SpeedUpRequest {
+    pub upgrade_id: i32,
+}
+
+async fn speed_up(
+    pool: web::Data<PgPool>,
+    redis_pool: web::Data<RedisPool>,
+    user: AuthUser,
+    req: Json<SpeedUpRequest>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let upgrade_id = req.upgrade_id;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest("You are under attack. Cannot speed up now"));
+    }
+
+    let upgrade = web::block(move || {
+        let mut conn = pool.get()?;
+        speed_up_upgrade(user_id, upgrade_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<UpgradeError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(upgrade))
+}
+
+#[derive(Deserialize, Serialize)]
+struct SellRequest {
+    pub item_type: ItemKind,
+    pub map_space_id: i32,
+}
+
+async fn sell(
+    pool: web::Data<PgPool>,
+    redis_pool: web::Data<RedisPool>,
+    user: AuthUser,
+    req: Json<SellRequest>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let item_type = req.item_type;
+    let map_space_id = req.map_space_id;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest("You are under attack. Cannot sell now"));
     }
-    Ok(Json(map_space_id_if_valid))
+
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        refund::sell_item(user_id, item_type, map_space_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<RefundError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(response))
+}
+
+async fn downgrade(
+    pool: web::Data<PgPool>,
+    redis_pool: web::Data<RedisPool>,
+    user: AuthUser,
+    req: Json<UpgradeStruct>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let item_type = req.item_type;
+    let item_id = req.item_id;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest(
+            "You are under attack. Cannot downgrade now",
+        ));
+    }
+
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        refund::downgrade_item(user_id, item_type, item_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<RefundError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(response))
+}
+
+async fn get_shop(user: AuthUser, pool: web::Data<PgPool>) -> Result<impl Responder> {
+    let user_id = user.0;
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        shop::fetch_shop(user_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, Serialize)]
+struct UnlockRequest {
+    pub unlockable_id: i32,
+}
+
+async fn unlock(
+    pool: web::Data<PgPool>,
+    redis_pool: web::Data<RedisPool>,
+    user: AuthUser,
+    req: Json<UnlockRequest>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let unlockable_id = req.unlockable_id;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    if let Ok(Some(_)) = get_game_id_from_redis(user_id, &mut redis_conn, false) {
+        return Err(ErrorBadRequest("You are under attack. Cannot unlock now"));
+    }
+
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        shop::unlock_item(user_id, unlockable_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| match err.downcast::<ShopError>() {
+        Ok(err) => err.into(),
+        Err(err) => error::handle_error(err.into()),
+    })?;
+
+    Ok(Json(response))
 }
@@ -0,0 +1,314 @@
+use super::builder::{fetch_upgrade_queue, resolve_upgrades, swap_item_level};
+use super::upgrade::{fetch_bank, fetch_item_levels, ItemKind, ItemLevel};
+use super::util::get_user_map_id;
+use crate::api::defense::util::{collect_production, get_building_capacity, lock_user_artifacts};
+use crate::api::error::RefundError;
+use crate::api::ledger::util::{move_artifacts, ArtifactAccount};
+use crate::constants::{
+    ATTACKER_REFUND_PERCENTAGE, BANK_BUILDING_NAME, BUILDING_REFUND_PERCENTAGE,
+    DEFENDER_REFUND_PERCENTAGE, EMP_REFUND_PERCENTAGE, MINE_REFUND_PERCENTAGE, ROAD_ID,
+};
+use crate::error::DieselError;
+use crate::models::{ArtifactLedgerReason, ItemCategory};
+use crate::schema::{artifact, block_type, building_type, map_spaces, unlockable};
+use crate::util::function;
+use anyhow::{Ok, Result};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RefundResponse {
+    pub item_type: ItemKind,
+    pub item_id: i32,
+    // The level the item went back to, None when it was sold
+    pub new_item_id: Option<i32>,
+    pub refund: i32,
+    pub artifacts_in_bank: i32,
+}
+
+fn refund_percentage(kind: ItemKind) -> i32 {
+    match kind {
+        ItemKind::Building => BUILDING_REFUND_PERCENTAGE,
+        ItemKind::Defender => DEFENDER_REFUND_PERCENTAGE,
+        ItemKind::Mine => MINE_REFUND_PERCENTAGE,
+        ItemKind::Attacker => ATTACKER_REFUND_PERCENTAGE,
+        ItemKind::Emp => EMP_REFUND_PERCENTAGE,
+    }
+}
+
+fn check_not_upgrading(
+    player_id: i32,
+    kind: ItemKind,
+    item_id: i32,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    if fetch_upgrade_queue(player_id, conn)?
+        .upgrades
+        .iter()
+        .any(|entry| entry.category == kind.category() && entry.from_id == item_id)
+    {
+        return Err(RefundError::Upgrading.into());
+    }
+    Ok(())
+}
+
+// (map space id, artifacts) of every placed block of the type in the player's base
+fn fetch_placed_blocks(
+    player_id: i32,
+    block_type_id: i32,
+    conn: &mut PgConnection,
+) -> Result<Vec<(i32, i32)>> {
+    let id_of_map = get_user_map_id(player_id, conn)?;
+    let placed = map_spaces::table
+        .left_join(artifact::table)
+        .filter(map_spaces::map_id.eq(id_of_map))
+        .filter(map_spaces::block_type_id.eq(block_type_id))
+        .select((map_spaces::id, artifact::count.nullable()))
+        .load::<(i32, Option<i32>)>(conn)
+        .map_err(|err| DieselError {
+            table: "map_spaces",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .map(|(map_space_id, count)| (map_space_id, count.unwrap_or(0)))
+        .collect();
+    Ok(placed)
+}
+
+// (block type id, artifacts) of the placed block, if the map space is in the player's base
+fn fetch_placed_block(
+    player_id: i32,
+    map_space_id: i32,
+    conn: &mut PgConnection,
+) -> Result<Option<(i32, i32)>> {
+    let id_of_map = get_user_map_id(player_id, conn)?;
+    let placed = map_spaces::table
+        .left_join(artifact::table)
+        .filter(map_spaces::id.eq(map_space_id))
+        .filter(map_spaces::map_id.eq(id_of_map))
+        .select((map_spaces::block_type_id, artifact::count.nullable()))
+        .first::<(i32, Option<i32>)>(conn)
+        .optional()
+        .map_err(|err| DieselError {
+            table: "map_spaces",
+            function: function!(),
+            error: err,
+        })?
+        .map(|(block_type_id, count)| (block_type_id, count.unwrap_or(0)));
+    Ok(placed)
+}
+
+// Artifacts spent on the item: its upgrades up to the current level and the cheapest shop price
+// of any of its levels
+fn fetch_item_value(kind: ItemKind, current: &ItemLevel, conn: &mut PgConnection) -> Result<i32> {
+    let levels: Vec<ItemLevel> = kind
+        .fetch_catalog(conn)?
+        .into_iter()
+        .filter(|item| item.name == current.name)
+        .collect();
+    let upgrades: i32 = levels
+        .iter()
+        .filter(|item| item.level < current.level)
+        .map(|item| item.cost)
+        .sum();
+
+    let ids: Vec<i32> = levels.iter().map(|item| item.id).collect();
+    let unlockables = unlockable::table.filter(unlockable::category.eq(kind.category()));
+    let unlock_cost = match kind.category() {
+        ItemCategory::Block => unlockables
+            .filter(unlockable::block_type_id.eq_any(ids))
+            .select(diesel::dsl::min(unlockable::cost))
+            .first::<Option<i32>>(conn),
+        ItemCategory::Attacker => unlockables
+            .filter(unlockable::attacker_type_id.eq_any(ids))
+            .select(diesel::dsl::min(unlockable::cost))
+            .first::<Option<i32>>(conn),
+        ItemCategory::Emp => unlockables
+            .filter(unlockable::emp_type_id.eq_any(ids))
+            .select(diesel::dsl::min(unlockable::cost))
+            .first::<Option<i32>>(conn),
+    }
+    .map_err(|err| DieselError {
+        table: "unlockable",
+        function: function!(),
+        error: err,
+    })?;
+
+    Ok(upgrades + unlock_cost.unwrap_or(0))
+}
+
+// Puts the kind's share of value into the bank, returns the refund and the artifacts in the bank
+fn pay_refund(
+    player_id: i32,
+    kind: ItemKind,
+    value: i32,
+    reference_id: i32,
+    conn: &mut PgConnection,
+) -> Result<(i32, i32)> {
+    let refund = value * refund_percentage(kind) / 100;
+    let (bank_map_space_id, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+    let artifacts_in_bank = artifacts_in_bank.max(0);
+    if artifacts_in_bank + refund > get_building_capacity(conn, &bank_map_space_id)? {
+        return Err(RefundError::BankFull.into());
+    }
+
+    move_artifacts(
+        ArtifactAccount::System,
+        ArtifactAccount::Building {
+            user_id: player_id,
+            map_space_id: bank_map_space_id,
+        },
+        refund,
+        ArtifactLedgerReason::Refund,
+        Some(reference_id),
+        conn,
+    )?;
+    Ok((refund, artifacts_in_bank + refund))
+}
+
+// Sells one placed building, defender or mine: it is removed from the base, the artifacts it holds
+// go to the bank and part of what the item cost is refunded. The item stays unlocked
+pub fn sell_item(
+    player_id: i32,
+    kind: ItemKind,
+    map_space_id: i32,
+    conn: &mut PgConnection,
+) -> Result<RefundResponse> {
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, player_id)?;
+        resolve_upgrades(player_id, conn)?;
+
+        if kind.category() != ItemCategory::Block {
+            return Err(RefundError::NotSellable(kind.to_string()).into());
+        }
+        let (item_id, stored) = fetch_placed_block(player_id, map_space_id, conn)?
+            .ok_or_else(|| RefundError::NotPlaced(kind.to_string()))?;
+        if !kind.is_owned(player_id, item_id, conn)? {
+            return Err(RefundError::NotOwned(kind.to_string()).into());
+        }
+        check_not_upgrading(player_id, kind, item_id, conn)?;
+
+        let (current, _) = fetch_item_levels(kind, item_id, conn)?;
+        let building_type_id = block_type::table
+            .find(item_id)
+            .select(block_type::building_type)
+            .first::<i32>(conn)
+            .map_err(|err| DieselError {
+                table: "block_type",
+                function: function!(),
+                error: err,
+            })?;
+        if building_type_id == ROAD_ID || current.name == BANK_BUILDING_NAME {
+            return Err(RefundError::NotSellable(current.name).into());
+        }
+
+        //uncollected production would be lost with the building
+        if kind == ItemKind::Building {
+            collect_production(conn, player_id)?;
+        }
+
+        let (bank_map_space_id, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+        if artifacts_in_bank.max(0) + stored > get_building_capacity(conn, &bank_map_space_id)? {
+            return Err(RefundError::ArtifactsDontFit.into());
+        }
+        move_artifacts(
+            ArtifactAccount::Building {
+                user_id: player_id,
+                map_space_id,
+            },
+            ArtifactAccount::Building {
+                user_id: player_id,
+                map_space_id: bank_map_space_id,
+            },
+            stored,
+            ArtifactLedgerReason::Transfer,
+            None,
+            conn,
+        )?;
+
+        diesel::delete(artifact::table.filter(artifact::map_space_id.eq(map_space_id)))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "artifact",
+                function: function!(),
+                error: err,
+            })?;
+        diesel::delete(map_spaces::table.find(map_space_id))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?;
+
+        let value = fetch_item_value(kind, &current, conn)?;
+        let (refund, artifacts_in_bank) = pay_refund(player_id, kind, value, item_id, conn)?;
+        Ok(RefundResponse {
+            item_type: kind,
+            item_id,
+            new_item_id: None,
+            refund,
+            artifacts_in_bank,
+        })
+    })
+}
+
+// Reverts the item to its previous level, refunding part of what the upgrade cost
+pub fn downgrade_item(
+    player_id: i32,
+    kind: ItemKind,
+    item_id: i32,
+    conn: &mut PgConnection,
+) -> Result<RefundResponse> {
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, player_id)?;
+        resolve_upgrades(player_id, conn)?;
+
+        if !kind.is_owned(player_id, item_id, conn)? {
+            return Err(RefundError::NotOwned(kind.to_string()).into());
+        }
+        check_not_upgrading(player_id, kind, item_id, conn)?;
+
+        let (current, _) = fetch_item_levels(kind, item_id, conn)?;
+        let previous = kind
+            .fetch_catalog(conn)?
+            .into_iter()
+            .find(|item| item.name == current.name && item.level == current.level - 1)
+            .ok_or(RefundError::LowestLevel(kind.to_string()))?;
+
+        if kind == ItemKind::Building {
+            collect_production(conn, player_id)?;
+
+            let previous_capacity = block_type::table
+                .inner_join(building_type::table)
+                .filter(block_type::id.eq(previous.id))
+                .select(building_type::capacity)
+                .first::<i32>(conn)
+                .map_err(|err| DieselError {
+                    table: "building_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            if fetch_placed_blocks(player_id, item_id, conn)?
+                .iter()
+                .any(|(_, artifacts)| *artifacts > previous_capacity)
+            {
+                return Err(RefundError::ArtifactsDontFit.into());
+            }
+        }
+
+        swap_item_level(player_id, kind.category(), current.id, previous.id, conn)?;
+
+        let (refund, artifacts_in_bank) =
+            pay_refund(player_id, kind, previous.cost, previous.id, conn)?;
+        Ok(RefundResponse {
+            item_type: kind,
+            item_id,
+            new_item_id: Some(previous.id),
+            refund,
+            artifacts_in_bank,
+        })
+    })
+}
@@ -0,0 +1,282 @@
+use super::builder::resolve_upgrades;
+use super::upgrade::{fetch_bank, ItemKind, ItemLevel};
+use super::util::{get_block_id_of_bank, get_inventory, InventoryResponse};
+use crate::api::defense::util::lock_user_artifacts;
+use crate::api::error::ShopError;
+use crate::api::ledger::util::{move_artifacts, ArtifactAccount};
+use crate::error::DieselError;
+use crate::models::{
+    ArtifactLedgerReason, AvailableBlocks, BlockCategory, ItemCategory, NewAvailableBlocks,
+    NewUnlockable, Unlockable,
+};
+use crate::schema::{available_blocks, block_type, building_type, unlockable, user};
+use crate::util::function;
+use anyhow::{Ok, Result};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize)]
+pub struct ShopItem {
+    pub unlockable_id: i32,
+    pub item_type: ItemKind,
+    pub item_id: i32,
+    pub name: String,
+    pub level: i32,
+    pub cost: i32,
+    pub min_trophies: i32,
+    pub min_bank_level: i32,
+    pub is_unlocked: bool,
+    pub can_unlock: bool,
+}
+
+#[derive(Serialize)]
+pub struct ShopResponse {
+    pub trophies: i32,
+    pub bank_level: i32,
+    pub artifacts_in_bank: i32,
+    pub items: Vec<ShopItem>,
+}
+
+// The bank is the centre of every base, its level gates what can be unlocked
+fn fetch_bank_level(player_id: i32, conn: &mut PgConnection) -> Result<i32> {
+    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
+    let level = block_type::table
+        .inner_join(building_type::table)
+        .filter(block_type::id.eq(bank_block_type_id))
+        .select(building_type::level)
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "building_type",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(level)
+}
+
+fn item_of(
+    unlockable: &Unlockable,
+    block_kinds: &HashMap<i32, ItemKind>,
+) -> Option<(ItemKind, i32)> {
+    match unlockable.category {
+        ItemCategory::Block => {
+            let block_type_id = unlockable.block_type_id?;
+            Some((*block_kinds.get(&block_type_id)?, block_type_id))
+        }
+        ItemCategory::Attacker => Some((ItemKind::Attacker, unlockable.attacker_type_id?)),
+        ItemCategory::Emp => Some((ItemKind::Emp, unlockable.emp_type_id?)),
+    }
+}
+
+// Every active unlockable and whether the player has it or could buy it now. A player has an item
+// when they own any level of it
+pub fn fetch_shop(player_id: i32, conn: &mut PgConnection) -> Result<ShopResponse> {
+    let unlockables = unlockable::table
+        .filter(unlockable::is_active.eq(true))
+        .order_by(unlockable::id.asc())
+        .load::<Unlockable>(conn)
+        .map_err(|err| DieselError {
+            table: "unlockable",
+            function: function!(),
+            error: err,
+        })?;
+
+    let block_kinds: HashMap<i32, ItemKind> = block_type::table
+        .select((block_type::id, block_type::category))
+        .load::<(i32, BlockCategory)>(conn)
+        .map_err(|err| DieselError {
+            table: "block_type",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .map(|(block_type_id, category)| {
+            let kind = match category {
+                BlockCategory::Building => ItemKind::Building,
+                BlockCategory::Defender => ItemKind::Defender,
+                BlockCategory::Mine => ItemKind::Mine,
+            };
+            (block_type_id, kind)
+        })
+        .collect();
+
+    let owned: HashSet<(ItemCategory, i32)> = available_blocks::table
+        .filter(available_blocks::user_id.eq(player_id))
+        .load::<AvailableBlocks>(conn)
+        .map_err(|err| DieselError {
+            table: "available_blocks",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .filter_map(|available_block| {
+            let item_id = match available_block.category {
+                ItemCategory::Block => available_block.block_type_id,
+                ItemCategory::Attacker => available_block.attacker_type_id,
+                ItemCategory::Emp => available_block.emp_type_id,
+            }?;
+            Some((available_block.category, item_id))
+        })
+        .collect();
+
+    let trophies = user::table
+        .find(player_id)
+        .select(user::trophies)
+        .first::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    let bank_level = fetch_bank_level(player_id, conn)?;
+    let (_, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+
+    let mut catalogs: HashMap<ItemKind, Vec<ItemLevel>> = HashMap::new();
+    let mut items = Vec::new();
+    for unlockable in unlockables {
+        let (kind, item_id) = match item_of(&unlockable, &block_kinds) {
+            Some(item) => item,
+            None => continue,
+        };
+        let catalog = match catalogs.entry(kind) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(kind.fetch_catalog(conn)?),
+        };
+        let item = match catalog.iter().find(|item| item.id == item_id) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        let is_unlocked = catalog
+            .iter()
+            .filter(|other| other.name == item.name)
+            .any(|other| owned.contains(&(kind.category(), other.id)));
+        items.push(ShopItem {
+            unlockable_id: unlockable.id,
+            item_type: kind,
+            item_id,
+            name: item.name.clone(),
+            level: item.level,
+            cost: unlockable.cost,
+            min_trophies: unlockable.min_trophies,
+            min_bank_level: unlockable.min_bank_level,
+            is_unlocked,
+            can_unlock: !is_unlocked
+                && trophies >= unlockable.min_trophies
+                && bank_level >= unlockable.min_bank_level
+                && artifacts_in_bank >= unlockable.cost,
+        });
+    }
+
+    Ok(ShopResponse {
+        trophies,
+        bank_level,
+        artifacts_in_bank,
+        items,
+    })
+}
+
+// Buys the item with artifacts from the bank and adds it to the player's inventory
+pub fn unlock_item(
+    player_id: i32,
+    unlockable_id: i32,
+    conn: &mut PgConnection,
+) -> Result<InventoryResponse> {
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, player_id)?;
+        resolve_upgrades(player_id, conn)?;
+
+        let shop = fetch_shop(player_id, conn)?;
+        let item = shop
+            .items
+            .iter()
+            .find(|item| item.unlockable_id == unlockable_id)
+            .ok_or(ShopError::UnlockableNotFound(unlockable_id))?;
+        if item.is_unlocked {
+            return Err(ShopError::AlreadyUnlocked.into());
+        }
+        if shop.trophies < item.min_trophies {
+            return Err(ShopError::NotEnoughTrophies(item.min_trophies).into());
+        }
+        if shop.bank_level < item.min_bank_level {
+            return Err(ShopError::BankLevelTooLow(item.min_bank_level).into());
+        }
+        if shop.artifacts_in_bank < item.cost {
+            return Err(ShopError::NotEnoughArtifactsInBank.into());
+        }
+
+        let (bank_map_space_id, _) = fetch_bank(player_id, conn)?;
+        move_artifacts(
+            ArtifactAccount::Building {
+                user_id: player_id,
+                map_space_id: bank_map_space_id,
+            },
+            ArtifactAccount::System,
+            item.cost,
+            ArtifactLedgerReason::Unlock,
+            Some(unlockable_id),
+            conn,
+        )?;
+
+        let category = item.item_type.category();
+        let id_if =
+            |item_category: ItemCategory| (category == item_category).then_some(item.item_id);
+        diesel::insert_into(available_blocks::table)
+            .values(NewAvailableBlocks {
+                block_type_id: id_if(ItemCategory::Block),
+                user_id: player_id,
+                attacker_type_id: id_if(ItemCategory::Attacker),
+                emp_type_id: id_if(ItemCategory::Emp),
+                category,
+            })
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "available_blocks",
+                function: function!(),
+                error: err,
+            })?;
+
+        get_inventory(player_id, conn)
+    })
+}
+
+// Puts an item in the shop, for designers adding content during a season
+pub fn add_unlockable(
+    kind: ItemKind,
+    item_id: i32,
+    cost: i32,
+    min_trophies: i32,
+    min_bank_level: i32,
+    conn: &mut PgConnection,
+) -> Result<Unlockable> {
+    if !kind
+        .fetch_catalog(conn)?
+        .iter()
+        .any(|item| item.id == item_id)
+    {
+        return Err(anyhow::anyhow!("There is no {kind} with id {item_id}"));
+    }
+
+    let category = kind.category();
+    let id_if = |item_category: ItemCategory| (category == item_category).then_some(item_id);
+    let unlockable = diesel::insert_into(unlockable::table)
+        .values(NewUnlockable {
+            category,
+            block_type_id: id_if(ItemCategory::Block),
+            attacker_type_id: id_if(ItemCategory::Attacker),
+            emp_type_id: id_if(ItemCategory::Emp),
+            cost,
+            min_trophies,
+            min_bank_level,
+            is_active: true,
+        })
+        .get_result::<Unlockable>(conn)
+        .map_err(|err| DieselError {
+            table: "unlockable",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(unlockable)
+}
@@ -0,0 +1,354 @@
+use super::builder::{fetch_upgrade_queue, queue_upgrade, resolve_upgrades, upgrade_duration};
+use super::util::{
+    fetch_item_stats, get_bank_map_space_id, get_block_id_of_bank, get_building_artifact_count,
+    get_user_artifacts, get_user_map_id, ItemStats,
+};
+use crate::api::defense::util::lock_user_artifacts;
+use crate::api::error::UpgradeError;
+use crate::error::DieselError;
+use crate::models::{BlockCategory, ItemCategory, UpgradeQueueEntry};
+use crate::schema::{
+    attacker_type, available_blocks, block_type, building_type, defender_type, emp_type,
+    map_spaces, mine_type,
+};
+use crate::util::function;
+use anyhow::{Ok, Result};
+use diesel::{dsl::exists, prelude::*, select, PgConnection};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Everything in the inventory that can be upgraded. Buildings, defenders and mines are owned as
+// block types, attackers and emps as their own types
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Building,
+    Defender,
+    Mine,
+    Attacker,
+    Emp,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ItemKind::Building => "building",
+            ItemKind::Defender => "defender",
+            ItemKind::Mine => "mine",
+            ItemKind::Attacker => "attacker",
+            ItemKind::Emp => "emp",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// One level of an item, id is what available_blocks holds for the kind
+#[derive(Clone, Debug)]
+pub struct ItemLevel {
+    pub id: i32,
+    pub name: String,
+    pub level: i32,
+    pub cost: i32,
+}
+
+impl ItemKind {
+    pub fn category(self) -> ItemCategory {
+        match self {
+            ItemKind::Building | ItemKind::Defender | ItemKind::Mine => ItemCategory::Block,
+            ItemKind::Attacker => ItemCategory::Attacker,
+            ItemKind::Emp => ItemCategory::Emp,
+        }
+    }
+
+    // Every level of every item of the kind
+    pub fn fetch_catalog(self, conn: &mut PgConnection) -> Result<Vec<ItemLevel>> {
+        let (table, levels) = match self {
+            ItemKind::Building => (
+                "building_type",
+                block_type::table
+                    .inner_join(building_type::table)
+                    .filter(block_type::category.eq(BlockCategory::Building))
+                    .select((
+                        block_type::id,
+                        building_type::name,
+                        building_type::level,
+                        building_type::cost,
+                    ))
+                    .load::<(i32, String, i32, i32)>(conn),
+            ),
+            ItemKind::Defender => (
+                "defender_type",
+                block_type::table
+                    .inner_join(defender_type::table)
+                    .filter(block_type::category.eq(BlockCategory::Defender))
+                    .select((
+                        block_type::id,
+                        defender_type::name,
+                        defender_type::level,
+                        defender_type::cost,
+                    ))
+                    .load::<(i32, String, i32, i32)>(conn),
+            ),
+            ItemKind::Mine => (
+                "mine_type",
+                block_type::table
+                    .inner_join(mine_type::table)
+                    .filter(block_type::category.eq(BlockCategory::Mine))
+                    .select((
+                        block_type::id,
+                        mine_type::name,
+                        mine_type::level,
+                        mine_type::cost,
+                    ))
+                    .load::<(i32, String, i32, i32)>(conn),
+            ),
+            ItemKind::Attacker => (
+                "attacker_type",
+                attacker_type::table
+                    .select((
+                        attacker_type::id,
+                        attacker_type::name,
+                        attacker_type::level,
+                        attacker_type::cost,
+                    ))
+                    .load::<(i32, String, i32, i32)>(conn),
+            ),
+            ItemKind::Emp => (
+                "emp_type",
+                emp_type::table
+                    .select((
+                        emp_type::id,
+                        emp_type::name,
+                        emp_type::level,
+                        emp_type::cost,
+                    ))
+                    .load::<(i32, String, i32, i32)>(conn),
+            ),
+        };
+
+        let levels = levels
+            .map_err(|err| DieselError {
+                table,
+                function: function!(),
+                error: err,
+            })?
+            .into_iter()
+            .map(|(id, name, level, cost)| ItemLevel {
+                id,
+                name,
+                level,
+                cost,
+            })
+            .collect();
+        Ok(levels)
+    }
+
+    pub fn is_owned(self, player_id: i32, item_id: i32, conn: &mut PgConnection) -> Result<bool> {
+        let player_available_blocks = available_blocks::table
+            .filter(available_blocks::user_id.eq(player_id))
+            .filter(available_blocks::category.eq(self.category()));
+        let owned = match self.category() {
+            ItemCategory::Block => select(exists(
+                player_available_blocks.filter(available_blocks::block_type_id.eq(item_id)),
+            ))
+            .get_result::<bool>(conn),
+            ItemCategory::Attacker => select(exists(
+                player_available_blocks.filter(available_blocks::attacker_type_id.eq(item_id)),
+            ))
+            .get_result::<bool>(conn),
+            ItemCategory::Emp => select(exists(
+                player_available_blocks.filter(available_blocks::emp_type_id.eq(item_id)),
+            ))
+            .get_result::<bool>(conn),
+        }
+        .map_err(|err| DieselError {
+            table: "available_blocks",
+            function: function!(),
+            error: err,
+        })?;
+        Ok(owned)
+    }
+}
+
+// The level the item is at and the one it would be upgraded to, None at max level
+pub fn fetch_item_levels(
+    kind: ItemKind,
+    item_id: i32,
+    conn: &mut PgConnection,
+) -> Result<(ItemLevel, Option<ItemLevel>)> {
+    let catalog = kind.fetch_catalog(conn)?;
+    let current = catalog
+        .iter()
+        .find(|item| item.id == item_id)
+        .cloned()
+        .ok_or(UpgradeError::NotOwned(kind.to_string()))?;
+    let next = catalog
+        .into_iter()
+        .find(|item| item.name == current.name && item.level == current.level + 1);
+    Ok((current, next))
+}
+
+// Id of the player's bank and the artifacts in it
+pub fn fetch_bank(player_id: i32, conn: &mut PgConnection) -> Result<(i32, i32)> {
+    let id_of_map = get_user_map_id(player_id, conn)?;
+    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
+    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
+    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
+    Ok((bank_map_space_id, artifacts_in_bank))
+}
+
+// Checks the upgrade and queues it with the builders, all in one transaction
+pub fn upgrade_item(
+    player_id: i32,
+    kind: ItemKind,
+    item_id: i32,
+    conn: &mut PgConnection,
+) -> Result<UpgradeQueueEntry> {
+    conn.transaction(|conn| {
+        lock_user_artifacts(conn, player_id)?;
+        resolve_upgrades(player_id, conn)?;
+
+        if !kind.is_owned(player_id, item_id, conn)? {
+            return Err(UpgradeError::NotOwned(kind.to_string()).into());
+        }
+        let (current, next) = fetch_item_levels(kind, item_id, conn)?;
+        let next = next.ok_or(UpgradeError::MaxLevel(kind.to_string()))?;
+
+        if current.cost > get_user_artifacts(player_id, conn)? {
+            return Err(UpgradeError::NotEnoughArtifacts.into());
+        }
+        let (bank_map_space_id, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+        if artifacts_in_bank < current.cost {
+            return Err(UpgradeError::NotEnoughArtifactsInBank.into());
+        }
+
+        queue_upgrade(
+            player_id,
+            kind.category(),
+            &current,
+            &next,
+            bank_map_space_id,
+            conn,
+        )
+    })
+}
+
+#[derive(Serialize)]
+pub struct StatChange {
+    pub stat: String,
+    pub current: serde_json::Value,
+    pub next: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct UpgradePreviewResponse {
+    pub item_type: ItemKind,
+    pub item_id: i32,
+    pub current: ItemStats,
+    pub next: Option<ItemStats>,
+    pub changes: Vec<StatChange>,
+    pub cost: i32,
+    pub artifacts: i32,
+    pub artifacts_in_bank: i32,
+    pub can_afford: bool,
+    pub is_upgrading: bool,
+    pub builder_available: bool,
+    pub upgrade_minutes: i64,
+    pub map_space_ids: Vec<i32>,
+}
+
+// Every stat that differs between the two levels, ids left out
+fn stat_changes(current: &ItemStats, next: &ItemStats) -> Result<Vec<StatChange>> {
+    let current = serde_json::to_value(current)?;
+    let next = serde_json::to_value(next)?;
+    let (current, next) = match (current.as_object(), next.as_object()) {
+        (Some(current), Some(next)) => (current.clone(), next.clone()),
+        _ => return Ok(Vec::new()),
+    };
+
+    let changes = current
+        .into_iter()
+        .filter(|(stat, _)| stat != "id" && stat != "block_id")
+        .filter_map(|(stat, current)| {
+            let next = next.get(&stat)?.clone();
+            (next != current).then_some(StatChange {
+                stat,
+                current,
+                next,
+            })
+        })
+        .collect();
+    Ok(changes)
+}
+
+// What upgrading the item would do, without changing anything but finishing due upgrades
+pub fn preview_upgrade(
+    player_id: i32,
+    kind: ItemKind,
+    item_id: i32,
+    conn: &mut PgConnection,
+) -> Result<UpgradePreviewResponse> {
+    resolve_upgrades(player_id, conn)?;
+
+    if !kind.is_owned(player_id, item_id, conn)? {
+        return Err(UpgradeError::NotOwned(kind.to_string()).into());
+    }
+    let (current_level, next_level) = fetch_item_levels(kind, item_id, conn)?;
+    let current = fetch_item_stats(kind, current_level.id, conn)?;
+    let next = match &next_level {
+        Some(next_level) => Some(fetch_item_stats(kind, next_level.id, conn)?),
+        None => None,
+    };
+    let changes = match &next {
+        Some(next) => stat_changes(&current, next)?,
+        None => Vec::new(),
+    };
+    let upgrade_minutes = match &next_level {
+        Some(next_level) => upgrade_duration(conn, next_level.level)?.num_minutes(),
+        None => 0,
+    };
+
+    let artifacts = get_user_artifacts(player_id, conn)?;
+    let (_, artifacts_in_bank) = fetch_bank(player_id, conn)?;
+    let cost = current_level.cost;
+
+    let upgrade_queue = fetch_upgrade_queue(player_id, conn)?;
+    let is_upgrading = upgrade_queue
+        .upgrades
+        .iter()
+        .any(|entry| entry.category == kind.category() && entry.from_id == item_id);
+    let builder_available = (upgrade_queue.upgrades.len() as i32) < upgrade_queue.builders;
+
+    let map_space_ids = if kind.category() == ItemCategory::Block {
+        let id_of_map = get_user_map_id(player_id, conn)?;
+        map_spaces::table
+            .filter(map_spaces::map_id.eq(id_of_map))
+            .filter(map_spaces::block_type_id.eq(item_id))
+            .order_by(map_spaces::id.asc())
+            .select(map_spaces::id)
+            .load::<i32>(conn)
+            .map_err(|err| DieselError {
+                table: "map_spaces",
+                function: function!(),
+                error: err,
+            })?
+    } else {
+        Vec::new()
+    };
+
+    Ok(UpgradePreviewResponse {
+        item_type: kind,
+        item_id,
+        can_afford: next.is_some() && cost <= artifacts && cost <= artifacts_in_bank,
+        current,
+        next,
+        changes,
+        cost,
+        artifacts,
+        artifacts_in_bank,
+        is_upgrading,
+        builder_available,
+        upgrade_minutes,
+        map_space_ids,
+    })
+}
@@ -1,8 +1,10 @@
+use super::builder::{fetch_upgrade_queue, resolve_upgrades};
+use super::upgrade::ItemKind;
 use crate::constants::BANK_BUILDING_NAME;
 use crate::error::DieselError;
 use crate::models::{
     AttackerType, BlockCategory, BlockType, BuildingType, DefenderType, EmpType, ItemCategory,
-    MineType,
+    MineType, UpgradeQueueEntry,
 };
 use crate::schema::{
     artifact, attacker_type, available_blocks, block_type, building_type, defender_type, emp_type,
@@ -11,7 +13,7 @@ use crate::schema::{
 use crate::schema::{map_layout, map_spaces, user};
 use crate::util::function;
 use anyhow::{Ok, Result};
-use diesel::{dsl::exists, prelude::*, select, PgConnection};
+use diesel::{prelude::*, PgConnection};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -25,6 +27,7 @@ pub struct BuildingTypeResponse {
     level: i32,
     cost: i32,
     hp: i32,
+    production_rate: i32,
     next_level_stats: Option<NextLevelBuildingTypeResponse>,
 }
 #[derive(Serialize, Deserialize)]
@@ -39,6 +42,7 @@ pub struct NextLevelBuildingTypeResponse {
     level: i32,
     cost: i32,
     hp: i32,
+    production_rate: i32,
 }
 #[derive(Serialize, Deserialize)]
 
@@ -142,9 +146,13 @@ pub struct InventoryResponse {
     defenders: Vec<DefenderTypeResponse>,
     mines: Vec<MineTypeResponse>,
     emps: Vec<EmpTypeResponse>,
+    builders: i32,
+    upgrades: Vec<UpgradeQueueEntry>,
 }
 
 pub fn get_inventory(player_id: i32, conn: &mut PgConnection) -> Result<InventoryResponse> {
+    resolve_upgrades(player_id, conn)?;
+    let upgrade_queue = fetch_upgrade_queue(player_id, conn)?;
     let buildings = get_building_types(player_id, conn)?;
     let attackers = get_attacker_types(player_id, conn)?;
     let defenders = get_defender_types(player_id, conn)?;
@@ -157,6 +165,8 @@ pub fn get_inventory(player_id: i32, conn: &mut PgConnection) -> Result<Inventor
         defenders,
         mines,
         emps,
+        builders: upgrade_queue.builders,
+        upgrades: upgrade_queue.upgrades,
     })
 }
 
@@ -202,6 +212,7 @@ fn get_building_types(
                     level: building_type.level,
                     cost: building_type.cost,
                     hp: building_type.hp,
+                    production_rate: building_type.production_rate,
                     next_level_stats: None,
                 }
             } else {
@@ -227,6 +238,7 @@ fn get_building_types(
                             level: 0,
                             cost: 0,
                             hp: 0,
+                            production_rate: 0,
                         },
                         BlockType {
                             id: 0,
@@ -247,6 +259,7 @@ fn get_building_types(
                     level: building_type.level,
                     cost: building_type.cost,
                     hp: building_type.hp,
+                    production_rate: building_type.production_rate,
                     next_level_stats: Some(NextLevelBuildingTypeResponse {
                         id: next_level_stats.0.id,
                         block_id: next_level_stats.1.id,
@@ -257,6 +270,7 @@ fn get_building_types(
                         level: next_level_stats.0.level,
                         cost: next_level_stats.0.cost,
                         hp: next_level_stats.0.hp,
+                        production_rate: next_level_stats.0.production_rate,
                     }),
                 }
             }
@@ -633,518 +647,131 @@ fn get_emp_types(player_id: i32, conn: &mut PgConnection) -> Result<Vec<EmpTypeR
     Ok(emps)
 }
 
-pub(crate) fn upgrade_building(
-    player_id: i32,
-    conn: &mut PgConnection,
-    block_id: i32,
-) -> Result<i32> {
-    let user_artifacts = get_user_artifacts(player_id, conn)?;
-
-    //check if the given block id is a building
-    //check if the given user has the block id
-    let exists = select(exists(
-        available_blocks::table
-            .inner_join(block_type::table)
-            .filter(available_blocks::user_id.eq(player_id))
-            .filter(available_blocks::block_type_id.eq(block_id))
-            .filter(available_blocks::category.eq(ItemCategory::Block))
-            .filter(block_type::category.eq(BlockCategory::Building)),
-    ))
-    .get_result::<bool>(conn)?;
-
-    if !exists {
-        return Err(anyhow::anyhow!(
-            "either Block is not a building or the user does not have the block"
-        ));
-    }
-
-    let joined_table = available_blocks::table
-        .inner_join(block_type::table.inner_join(building_type::table))
-        .filter(available_blocks::user_id.eq(player_id))
-        .filter(available_blocks::category.eq(ItemCategory::Block))
-        .filter(block_type::category.eq(BlockCategory::Building));
-
-    let (cost, level, name): (i32, i32, String) = joined_table
-        .clone()
-        .filter(available_blocks::block_type_id.eq(block_id))
-        .select((
-            building_type::cost,
-            building_type::level,
-            building_type::name,
-        ))
-        .first::<(i32, i32, String)>(conn)
-        .map_err(|err| DieselError {
-            table: "building_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let max_level: i64 = building_type::table
-        .filter(building_type::name.eq(&name))
-        .count()
-        .get_result::<i64>(conn)
-        .map_err(|err| DieselError {
-            table: "building_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    if level >= max_level as i32 {
-        return Err(anyhow::anyhow!("Building is at max level"));
-    };
-    if cost > user_artifacts {
-        return Err(anyhow::anyhow!("Not enough artifacts"));
-    };
-
-    let joined_table = block_type::table
-        .inner_join(building_type::table)
-        .filter(block_type::category.eq(BlockCategory::Building));
-
-    let next_level_block_id: i32 = joined_table
-        .filter(building_type::name.eq(name))
-        .filter(building_type::level.eq(level + 1))
-        .select(block_type::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "available_blocks",
-            function: function!(),
-            error: err,
-        })?;
-
-    let id_of_map = get_user_map_id(player_id, conn)?;
-    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
-    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
-    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
-    if artifacts_in_bank < cost {
-        return Err(anyhow::anyhow!("Not enough artifacts in bank"));
-    }
-    let _ = run_transaction(
-        conn,
-        block_id,
-        next_level_block_id,
-        player_id,
-        cost,
-        user_artifacts,
-        bank_map_space_id,
-    );
-    let building_map_space_id = get_building_map_space_id(conn, &id_of_map, &next_level_block_id)?;
-    Ok(building_map_space_id)
-}
-
-pub(crate) fn upgrade_defender(
-    player_id: i32,
-    conn: &mut PgConnection,
-    block_id: i32,
-) -> Result<()> {
-    let user_artifacts = get_user_artifacts(player_id, conn)?;
-
-    //check if the given block id is a defender
-    //check if the given user has the block id
-    let exists = select(exists(
-        available_blocks::table
-            .inner_join(block_type::table)
-            .filter(available_blocks::user_id.eq(player_id))
-            .filter(available_blocks::block_type_id.eq(block_id))
-            .filter(available_blocks::category.eq(ItemCategory::Block))
-            .filter(block_type::category.eq(BlockCategory::Defender)),
-    ))
-    .get_result::<bool>(conn)?;
-
-    if !exists {
-        return Err(anyhow::anyhow!(
-            "either Block is not a defender or the user does not have the block"
-        ));
-    }
-
-    let joined_table = available_blocks::table
-        .inner_join(block_type::table.inner_join(defender_type::table))
-        .filter(available_blocks::user_id.eq(player_id))
-        .filter(available_blocks::category.eq(ItemCategory::Block))
-        .filter(block_type::category.eq(BlockCategory::Defender));
-
-    let (cost, level, name): (i32, i32, String) = joined_table
-        .clone()
-        .filter(available_blocks::block_type_id.eq(block_id))
-        .select((
-            defender_type::cost,
-            defender_type::level,
-            defender_type::name,
-        ))
-        .first::<(i32, i32, String)>(conn)
-        .map_err(|err| DieselError {
-            table: "defender_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let max_level: i64 = defender_type::table
-        .filter(defender_type::name.eq(&name))
-        .count()
-        .get_result::<i64>(conn)
-        .map_err(|err| DieselError {
-            table: "defender_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    if level >= max_level as i32 {
-        return Err(anyhow::anyhow!("Defender is at max level"));
-    };
-    if cost > user_artifacts {
-        return Err(anyhow::anyhow!("Not enough artifacts"));
-    };
-
-    let joined_table = block_type::table
-        .inner_join(defender_type::table)
-        .filter(block_type::category.eq(BlockCategory::Defender));
-
-    let next_level_block_id: i32 = joined_table
-        .filter(defender_type::name.eq(name))
-        .filter(defender_type::level.eq(level + 1))
-        .select(block_type::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "available_blocks",
-            function: function!(),
-            error: err,
-        })?;
-
-    let id_of_map = get_user_map_id(player_id, conn)?;
-    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
-    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
-    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
-    if artifacts_in_bank < cost {
-        return Err(anyhow::anyhow!("Not enough artifacts in bank"));
-    }
-    run_transaction(
-        conn,
-        block_id,
-        next_level_block_id,
-        player_id,
-        cost,
-        user_artifacts,
-        bank_map_space_id,
-    )
-}
-
-pub(crate) fn upgrade_mine(player_id: i32, conn: &mut PgConnection, block_id: i32) -> Result<()> {
-    let user_artifacts = get_user_artifacts(player_id, conn)?;
-
-    //check if the given block id is a mine
-    //check if the given user has the block id
-    let exists = select(exists(
-        available_blocks::table
-            .inner_join(block_type::table)
-            .filter(available_blocks::user_id.eq(player_id))
-            .filter(available_blocks::block_type_id.eq(block_id))
-            .filter(available_blocks::category.eq(ItemCategory::Block))
-            .filter(block_type::category.eq(BlockCategory::Mine)),
-    ))
-    .get_result::<bool>(conn)?;
-
-    if !exists {
-        return Err(anyhow::anyhow!(
-            "either Block is not a mine or the user does not have the block"
-        ));
-    }
-
-    let joined_table = available_blocks::table
-        .inner_join(block_type::table.inner_join(mine_type::table))
-        .filter(available_blocks::user_id.eq(player_id))
-        .filter(available_blocks::category.eq(ItemCategory::Block))
-        .filter(block_type::category.eq(BlockCategory::Mine));
-
-    let (cost, level, name): (i32, i32, String) = joined_table
-        .clone()
-        .filter(available_blocks::block_type_id.eq(block_id))
-        .select((mine_type::cost, mine_type::level, mine_type::name))
-        .first::<(i32, i32, String)>(conn)
-        .map_err(|err| DieselError {
-            table: "mine_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let max_level: i64 = mine_type::table
-        .filter(mine_type::name.eq(&name))
-        .count()
-        .get_result::<i64>(conn)
-        .map_err(|err| DieselError {
-            table: "mine_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    if level >= max_level as i32 {
-        return Err(anyhow::anyhow!("Defender is at max level"));
-    };
-    if cost > user_artifacts {
-        return Err(anyhow::anyhow!("Not enough artifacts"));
-    };
-
-    let joined_table = block_type::table
-        .inner_join(mine_type::table)
-        .filter(block_type::category.eq(BlockCategory::Mine));
-
-    let next_level_block_id: i32 = joined_table
-        .filter(mine_type::name.eq(name))
-        .filter(mine_type::level.eq(level + 1))
-        .select(block_type::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "available_blocks",
-            function: function!(),
-            error: err,
-        })?;
-
-    let id_of_map = get_user_map_id(player_id, conn)?;
-    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
-    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
-    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
-    if artifacts_in_bank < cost {
-        return Err(anyhow::anyhow!("Not enough artifacts in bank"));
-    }
-    run_transaction(
-        conn,
-        block_id,
-        next_level_block_id,
-        player_id,
-        cost,
-        user_artifacts,
-        bank_map_space_id,
-    )
+// Stats of one level of an item, in the shape the inventory shows next levels in
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ItemStats {
+    Building(NextLevelBuildingTypeResponse),
+    Defender(NextLevelDefenderTypeResponse),
+    Mine(NextLevelMineTypeResponse),
+    Attacker(NextLevelAttackerTypeResponse),
+    Emp(NextLevelEmpTypeResponse),
 }
 
-pub(crate) fn upgrade_attacker(
-    player_id: i32,
+// item_id is a block type id for buildings, defenders and mines
+pub fn fetch_item_stats(
+    kind: ItemKind,
+    item_id: i32,
     conn: &mut PgConnection,
-    attacker_id: i32,
-) -> Result<()> {
-    let user_artifacts = get_user_artifacts(player_id, conn)?;
-
-    let joined_table = available_blocks::table
-        .inner_join(attacker_type::table)
-        .filter(available_blocks::user_id.eq(player_id))
-        .filter(available_blocks::category.eq(ItemCategory::Attacker));
-
-    let exists = select(exists(
-        joined_table
-            .clone()
-            .filter(attacker_type::id.eq(attacker_id)),
-    ))
-    .get_result::<bool>(conn)?;
-
-    if !exists {
-        return Err(anyhow::anyhow!("User does not have the attacker"));
-    }
-
-    let (cost, level, name): (i32, i32, String) = joined_table
-        .filter(attacker_type::id.eq(attacker_id))
-        .select((
-            attacker_type::cost,
-            attacker_type::level,
-            attacker_type::name,
-        ))
-        .first::<(i32, i32, String)>(conn)
-        .map_err(|err| DieselError {
-            table: "attacker_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let max_level: i64 = attacker_type::table
-        .filter(attacker_type::name.eq(&name))
-        .count()
-        .get_result::<i64>(conn)
-        .map_err(|err| DieselError {
-            table: "attacker_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    if level >= max_level as i32 {
-        return Err(anyhow::anyhow!("Attacker is at max level"));
-    };
-    if cost > user_artifacts {
-        return Err(anyhow::anyhow!("Not enough artifacts"));
-    };
-    let id_of_map = get_user_map_id(player_id, conn)?;
-    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
-    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
-    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
-    if artifacts_in_bank < cost {
-        return Err(anyhow::anyhow!("Not enough artifacts in bank"));
-    }
-    let next_level_attacker_id: i32 = attacker_type::table
-        .filter(attacker_type::name.eq(name))
-        .filter(attacker_type::level.eq(level + 1))
-        .select(attacker_type::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "attacker_type",
-            function: function!(),
-            error: err,
-        })?;
-    conn.transaction(|conn| {
-        diesel::update(
-            available_blocks::table
-                .filter(available_blocks::attacker_type_id.eq(attacker_id))
-                .filter(available_blocks::user_id.eq(player_id)),
-        )
-        .set(available_blocks::attacker_type_id.eq(next_level_attacker_id))
-        .execute(conn)?;
-
-        //update artifacts in bank
-        diesel::update(artifact::table.filter(artifact::map_space_id.eq(bank_map_space_id)))
-            .set(artifact::count.eq(artifact::count - cost))
-            .execute(conn)
-            .map_err(|err| DieselError {
-                table: "artifact",
-                function: function!(),
-                error: err,
-            })?;
-
-        diesel::update(user::table.filter(user::id.eq(player_id)))
-            .set(user::artifacts.eq(user_artifacts - cost))
-            .execute(conn)?;
-
-        Ok(())
-    })
-}
-
-pub(crate) fn upgrade_emp(player_id: i32, conn: &mut PgConnection, emp_id: i32) -> Result<()> {
-    let user_artifacts = get_user_artifacts(player_id, conn)?;
-
-    let joined_table = available_blocks::table
-        .inner_join(emp_type::table)
-        .filter(available_blocks::user_id.eq(player_id))
-        .filter(available_blocks::category.eq(ItemCategory::Emp));
-
-    let exists = select(exists(joined_table.clone().filter(emp_type::id.eq(emp_id))))
-        .get_result::<bool>(conn)?;
-
-    if !exists {
-        return Err(anyhow::anyhow!("User does not have the emp"));
-    }
-
-    let (cost, level, name): (i32, i32, String) = joined_table
-        .filter(emp_type::id.eq(emp_id))
-        .select((emp_type::cost, emp_type::level, emp_type::name))
-        .first::<(i32, i32, String)>(conn)
-        .map_err(|err| DieselError {
-            table: "emp_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let max_level: i64 = emp_type::table
-        .filter(emp_type::name.eq(&name))
-        .count()
-        .get_result::<i64>(conn)
-        .map_err(|err| DieselError {
-            table: "emp_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    if level >= max_level as i32 {
-        return Err(anyhow::anyhow!("Emp is at max level"));
-    };
-    if cost > user_artifacts {
-        return Err(anyhow::anyhow!("Not enough artifacts"));
+) -> Result<ItemStats> {
+    let stats = match kind {
+        ItemKind::Building => {
+            let building_type = block_type::table
+                .inner_join(building_type::table)
+                .filter(block_type::id.eq(item_id))
+                .select(building_type::all_columns)
+                .first::<BuildingType>(conn)
+                .map_err(|err| DieselError {
+                    table: "building_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            ItemStats::Building(NextLevelBuildingTypeResponse {
+                id: building_type.id,
+                block_id: item_id,
+                name: building_type.name,
+                width: building_type.width,
+                height: building_type.height,
+                capacity: building_type.capacity,
+                level: building_type.level,
+                cost: building_type.cost,
+                hp: building_type.hp,
+                production_rate: building_type.production_rate,
+            })
+        }
+        ItemKind::Defender => {
+            let defender_type = block_type::table
+                .inner_join(defender_type::table)
+                .filter(block_type::id.eq(item_id))
+                .select(defender_type::all_columns)
+                .first::<DefenderType>(conn)
+                .map_err(|err| DieselError {
+                    table: "defender_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            ItemStats::Defender(NextLevelDefenderTypeResponse {
+                id: defender_type.id,
+                block_id: item_id,
+                speed: defender_type.speed,
+                damage: defender_type.damage,
+                radius: defender_type.radius,
+                level: defender_type.level,
+                cost: defender_type.cost,
+                name: defender_type.name,
+            })
+        }
+        ItemKind::Mine => {
+            let mine_type = block_type::table
+                .inner_join(mine_type::table)
+                .filter(block_type::id.eq(item_id))
+                .select(mine_type::all_columns)
+                .first::<MineType>(conn)
+                .map_err(|err| DieselError {
+                    table: "mine_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            ItemStats::Mine(NextLevelMineTypeResponse {
+                id: mine_type.id,
+                block_id: item_id,
+                radius: mine_type.radius,
+                damage: mine_type.damage,
+                level: mine_type.level,
+                cost: mine_type.cost,
+                name: mine_type.name,
+            })
+        }
+        ItemKind::Attacker => {
+            let attacker_type = attacker_type::table
+                .find(item_id)
+                .first::<AttackerType>(conn)
+                .map_err(|err| DieselError {
+                    table: "attacker_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            ItemStats::Attacker(NextLevelAttackerTypeResponse {
+                id: attacker_type.id,
+                max_health: attacker_type.max_health,
+                speed: attacker_type.speed,
+                amt_of_emps: attacker_type.amt_of_emps,
+                level: attacker_type.level,
+                cost: attacker_type.cost,
+                name: attacker_type.name,
+            })
+        }
+        ItemKind::Emp => {
+            let emp_type = emp_type::table
+                .find(item_id)
+                .first::<EmpType>(conn)
+                .map_err(|err| DieselError {
+                    table: "emp_type",
+                    function: function!(),
+                    error: err,
+                })?;
+            ItemStats::Emp(NextLevelEmpTypeResponse {
+                id: emp_type.id,
+                att_type: emp_type.att_type,
+                attack_radius: emp_type.attack_radius,
+                attack_damage: emp_type.attack_damage,
+                cost: emp_type.cost,
+                name: emp_type.name,
+                level: emp_type.level,
+            })
+        }
     };
-
-    let next_level_emp_id: i32 = emp_type::table
-        .filter(emp_type::name.eq(name))
-        .filter(emp_type::level.eq(level + 1))
-        .select(emp_type::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "emp_type",
-            function: function!(),
-            error: err,
-        })?;
-
-    let id_of_map = get_user_map_id(player_id, conn)?;
-    let bank_block_type_id = get_block_id_of_bank(conn, &player_id)?;
-    let bank_map_space_id = get_bank_map_space_id(conn, &id_of_map, &bank_block_type_id)?;
-    let artifacts_in_bank = get_building_artifact_count(conn, &id_of_map, &bank_map_space_id)?;
-    if artifacts_in_bank < cost {
-        return Err(anyhow::anyhow!("Not enough artifacts in bank"));
-    }
-    conn.transaction(|conn| {
-        diesel::update(
-            available_blocks::table
-                .filter(available_blocks::emp_type_id.eq(emp_id))
-                .filter(available_blocks::user_id.eq(player_id)),
-        )
-        .set(available_blocks::emp_type_id.eq(next_level_emp_id))
-        .execute(conn)?;
-
-        //update artifacts in bank
-        diesel::update(artifact::table.filter(artifact::map_space_id.eq(bank_map_space_id)))
-            .set(artifact::count.eq(artifact::count - cost))
-            .execute(conn)
-            .map_err(|err| DieselError {
-                table: "artifact",
-                function: function!(),
-                error: err,
-            })?;
-
-        diesel::update(user::table.filter(user::id.eq(player_id)))
-            .set(user::artifacts.eq(user_artifacts - cost))
-            .execute(conn)?;
-
-        Ok(())
-    })
-}
-
-#[warn(clippy::too_many_arguments)]
-fn run_transaction(
-    conn: &mut PgConnection,
-    block_id: i32,
-    next_level_block_id: i32,
-    player_id: i32,
-    cost: i32,
-    user_artifacts: i32,
-    bank_map_space_id: i32,
-) -> Result<(), anyhow::Error> {
-    conn.transaction(|conn| {
-        let id_of_map = get_user_map_id(player_id, conn)?;
-
-        diesel::update(
-            available_blocks::table
-                .filter(available_blocks::block_type_id.eq(block_id))
-                .filter(available_blocks::user_id.eq(player_id)),
-        )
-        .set(available_blocks::block_type_id.eq(next_level_block_id))
-        .execute(conn)?;
-
-        diesel::update(user::table.filter(user::id.eq(player_id)))
-            .set(user::artifacts.eq(user_artifacts - cost))
-            .execute(conn)?;
-
-        //update artifacts in bank
-        diesel::update(artifact::table.filter(artifact::map_space_id.eq(bank_map_space_id)))
-            .set(artifact::count.eq(artifact::count - cost))
-            .execute(conn)
-            .map_err(|err| DieselError {
-                table: "artifact",
-                function: function!(),
-                error: err,
-            })?;
-
-        //update map spaces
-        diesel::update(
-            map_spaces::table
-                .filter(map_spaces::block_type_id.eq(block_id))
-                .filter(map_spaces::map_id.eq(id_of_map)),
-        )
-        .set(map_spaces::block_type_id.eq(next_level_block_id))
-        .execute(conn)?;
-
-        Ok(())
-    })
+    Ok(stats)
 }
 
 pub fn get_user_map_id(player_id: i32, conn: &mut PgConnection) -> Result<i32> {
@@ -1223,21 +850,3 @@ pub fn get_bank_map_space_id(
         })?;
     Ok(fetched_bank_map_space_id)
 }
-
-pub fn get_building_map_space_id(
-    conn: &mut PgConnection,
-    filtered_layout_id: &i32,
-    block_id: &i32,
-) -> Result<i32> {
-    let fetched_building_map_space_id = map_spaces::table
-        .filter(map_spaces::map_id.eq(filtered_layout_id))
-        .filter(map_spaces::block_type_id.eq(block_id))
-        .select(map_spaces::id)
-        .first::<i32>(conn)
-        .map_err(|err| DieselError {
-            table: "map_spaces",
-            function: function!(),
-            error: err,
-        })?;
-    Ok(fetched_building_map_space_id)
-}
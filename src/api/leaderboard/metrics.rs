@@ -0,0 +1,332 @@
+use crate::api::RedisConn;
+use crate::constants::{LEADERBOARD_KEY, METRIC_LEADERBOARD_CACHE_SECONDS};
+use crate::error::DieselError;
+use crate::schema::game;
+use crate::util::function;
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use diesel::prelude::*;
+use diesel::query_dsl::methods;
+use diesel::PgConnection;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardMetric {
+    TrophiesGained,
+    ArtifactsLooted,
+    AttacksWon,
+    DefensesWon,
+    HighestDamage,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardPeriod {
+    Day,
+    #[default]
+    Week,
+    AllTime,
+}
+
+#[derive(Deserialize)]
+pub struct MetricLeaderboardQuery {
+    pub period: Option<LeaderboardPeriod>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct MetricLeaderboardEntry {
+    pub rank: i64,
+    pub user_id: i32,
+    pub name: String,
+    pub avatar_url: i32,
+    pub value: i64,
+}
+
+#[derive(Serialize)]
+pub struct MetricLeaderboardResponse {
+    pub metric: LeaderboardMetric,
+    pub period: LeaderboardPeriod,
+    pub since: Option<NaiveDate>,
+    pub leaderboard_entries: Vec<MetricLeaderboardEntry>,
+    pub last_page: i64,
+}
+
+impl LeaderboardMetric {
+    fn key(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::TrophiesGained => "trophies_gained",
+            LeaderboardMetric::ArtifactsLooted => "artifacts_looted",
+            LeaderboardMetric::AttacksWon => "attacks_won",
+            LeaderboardMetric::DefensesWon => "defenses_won",
+            LeaderboardMetric::HighestDamage => "highest_damage",
+        }
+    }
+}
+
+impl LeaderboardPeriod {
+    fn key(&self) -> &'static str {
+        match self {
+            LeaderboardPeriod::Day => "day",
+            LeaderboardPeriod::Week => "week",
+            LeaderboardPeriod::AllTime => "all_time",
+        }
+    }
+
+    // First day counted by the period, weeks start on monday
+    pub fn since(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            LeaderboardPeriod::Day => Some(today),
+            LeaderboardPeriod::Week => {
+                Some(today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64))
+            }
+            LeaderboardPeriod::AllTime => None,
+        }
+    }
+}
+
+// Narrows a boxed aggregate over finished games to the period, all-time rankings skip the filter
+fn played_since<Q>(query: Q, since: Option<NaiveDate>) -> Q
+where
+    Q: methods::FilterDsl<diesel::dsl::GtEq<game::date, NaiveDate>, Output = Q>,
+{
+    match since {
+        Some(since) => methods::FilterDsl::filter(query, game::date.ge(since)),
+        None => query,
+    }
+}
+
+// (user_id, value) pairs of every player with a non-zero value, best first
+fn compute_metric(
+    metric: LeaderboardMetric,
+    since: Option<NaiveDate>,
+    conn: &mut PgConnection,
+) -> Result<Vec<(i32, i64)>> {
+    let finished_games = game::table
+        .filter(game::is_game_over.eq(true))
+        .filter(game::is_practice.eq(false));
+
+    let mut values: HashMap<i32, i64> = HashMap::new();
+    match metric {
+        LeaderboardMetric::TrophiesGained => {
+            let attacks = played_since(
+                finished_games
+                    .group_by(game::attack_id)
+                    .select((game::attack_id, diesel::dsl::sum(game::attack_score)))
+                    .into_boxed(),
+                since,
+            )
+            .load::<(i32, Option<i64>)>(conn)
+            .map_err(|err| DieselError {
+                table: "game",
+                function: function!(),
+                error: err,
+            })?;
+            let defenses = played_since(
+                finished_games
+                    .group_by(game::defend_id)
+                    .select((game::defend_id, diesel::dsl::sum(game::defend_score)))
+                    .into_boxed(),
+                since,
+            )
+            .load::<(i32, Option<i64>)>(conn)
+            .map_err(|err| DieselError {
+                table: "game",
+                function: function!(),
+                error: err,
+            })?;
+            for (user_id, trophies) in attacks.into_iter().chain(defenses) {
+                *values.entry(user_id).or_insert(0) += trophies.unwrap_or(0);
+            }
+        }
+        LeaderboardMetric::ArtifactsLooted => {
+            values.extend(
+                played_since(
+                    finished_games
+                        .group_by(game::attack_id)
+                        .select((game::attack_id, diesel::dsl::sum(game::artifacts_collected)))
+                        .into_boxed(),
+                    since,
+                )
+                .load::<(i32, Option<i64>)>(conn)
+                .map_err(|err| DieselError {
+                    table: "game",
+                    function: function!(),
+                    error: err,
+                })?
+                .into_iter()
+                .map(|(user_id, artifacts)| (user_id, artifacts.unwrap_or(0))),
+            );
+        }
+        LeaderboardMetric::AttacksWon => {
+            values.extend(
+                played_since(
+                    finished_games
+                        .filter(game::stars.gt(0))
+                        .group_by(game::attack_id)
+                        .select((game::attack_id, diesel::dsl::count(game::id)))
+                        .into_boxed(),
+                    since,
+                )
+                .load::<(i32, i64)>(conn)
+                .map_err(|err| DieselError {
+                    table: "game",
+                    function: function!(),
+                    error: err,
+                })?,
+            );
+        }
+        LeaderboardMetric::DefensesWon => {
+            values.extend(
+                played_since(
+                    finished_games
+                        .filter(game::stars.eq(0))
+                        .group_by(game::defend_id)
+                        .select((game::defend_id, diesel::dsl::count(game::id)))
+                        .into_boxed(),
+                    since,
+                )
+                .load::<(i32, i64)>(conn)
+                .map_err(|err| DieselError {
+                    table: "game",
+                    function: function!(),
+                    error: err,
+                })?,
+            );
+        }
+        LeaderboardMetric::HighestDamage => {
+            values.extend(
+                played_since(
+                    finished_games
+                        .group_by(game::attack_id)
+                        .select((game::attack_id, diesel::dsl::max(game::damage_done)))
+                        .into_boxed(),
+                    since,
+                )
+                .load::<(i32, Option<i32>)>(conn)
+                .map_err(|err| DieselError {
+                    table: "game",
+                    function: function!(),
+                    error: err,
+                })?
+                .into_iter()
+                .map(|(user_id, damage)| (user_id, damage.unwrap_or(0) as i64)),
+            );
+        }
+    }
+
+    let mut ranking: Vec<(i32, i64)> = values
+        .into_iter()
+        .filter(|(_, value)| *value != 0)
+        .collect();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok(ranking)
+}
+
+// The ranking is cached in redis for a few minutes as the aggregation scans every game in the period
+fn get_ranking(
+    metric: LeaderboardMetric,
+    period: LeaderboardPeriod,
+    since: Option<NaiveDate>,
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+) -> Result<Vec<(i32, i64)>> {
+    let cache_key = format!(
+        "{}:{}:{}:{}",
+        LEADERBOARD_KEY,
+        metric.key(),
+        period.key(),
+        since.map_or(String::new(), |since| since.to_string())
+    );
+
+    let cached: Option<String> = redis_conn.get(&cache_key)?;
+    if let Some(ranking) = cached.and_then(|cached| serde_json::from_str(&cached).ok()) {
+        return Ok(ranking);
+    }
+
+    let ranking = compute_metric(metric, since, conn)?;
+    redis_conn.set_ex::<_, _, ()>(
+        &cache_key,
+        serde_json::to_string(&ranking)?,
+        METRIC_LEADERBOARD_CACHE_SECONDS,
+    )?;
+    Ok(ranking)
+}
+
+pub fn get_metric_leaderboard(
+    metric: LeaderboardMetric,
+    period: LeaderboardPeriod,
+    page: i64,
+    limit: i64,
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+) -> Result<MetricLeaderboardResponse> {
+    use crate::schema::user;
+
+    let since = period.since(chrono::Local::now().date_naive());
+    let bots: Vec<i32> = user::table
+        .filter(user::is_pragyan.eq(true))
+        .select(user::id)
+        .load::<i32>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+    let ranking: Vec<(i32, i64)> = get_ranking(metric, period, since, conn, redis_conn)?
+        .into_iter()
+        .filter(|(user_id, _)| !bots.contains(user_id))
+        .collect();
+
+    let off_set: i64 = (page - 1) * limit;
+    let last_page: i64 = (ranking.len() as f64 / limit as f64).ceil() as i64;
+    let page_entries: Vec<(i64, (i32, i64))> = (1..)
+        .zip(ranking)
+        .skip(off_set as usize)
+        .take(limit as usize)
+        .collect();
+
+    let user_ids: Vec<i32> = page_entries
+        .iter()
+        .map(|(_, (user_id, _))| *user_id)
+        .collect();
+    let mut details: HashMap<i32, (String, i32)> = user::table
+        .filter(user::id.eq_any(user_ids))
+        .select((user::id, user::username, user::avatar_id))
+        .load::<(i32, String, i32)>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .map(|(id, name, avatar_id)| (id, (name, avatar_id)))
+        .collect();
+
+    let leaderboard_entries = page_entries
+        .into_iter()
+        .filter_map(|(rank, (user_id, value))| {
+            details
+                .remove(&user_id)
+                .map(|(name, avatar_id)| MetricLeaderboardEntry {
+                    rank,
+                    user_id,
+                    name,
+                    avatar_url: avatar_id,
+                    value,
+                })
+        })
+        .collect();
+
+    Ok(MetricLeaderboardResponse {
+        metric,
+        period,
+        since,
+        leaderboard_entries,
+        last_page,
+    })
+}
@@ -0,0 +1,104 @@
+use super::{error, PgPool, RedisPool};
+use crate::constants::{LEADERBOARD_AROUND_RANGE, LEADERBOARD_TOP_LIMIT};
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::web::{self, Data, Json, Path};
+use actix_web::{Responder, Result};
+use metrics::{LeaderboardMetric, MetricLeaderboardQuery};
+use serde::Deserialize;
+
+pub mod metrics;
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/top").route(web::get().to(get_top)))
+        .service(web::resource("/{user_id}/rank").route(web::get().to(get_rank)))
+        .service(web::resource("/{user_id}/around").route(web::get().to(get_around)))
+        .service(web::resource("/metrics/{metric}").route(web::get().to(get_metric_leaderboard)));
+}
+
+#[derive(Deserialize)]
+pub struct TopQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct AroundQuery {
+    pub range: Option<i64>,
+}
+
+async fn get_top(
+    query: web::Query<TopQuery>,
+    pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+) -> Result<impl Responder> {
+    let limit = query.limit.unwrap_or(10);
+    if limit <= 0 || limit > LEADERBOARD_TOP_LIMIT {
+        return Err(ErrorBadRequest("Invalid query params"));
+    }
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        let mut redis_conn = redis_pool.get()?;
+        util::fetch_top(limit, &mut conn, &mut redis_conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
+
+async fn get_rank(user_id: Path<i32>, redis_pool: Data<RedisPool>) -> Result<impl Responder> {
+    let user_id = user_id.into_inner();
+    let response = web::block(move || {
+        let mut redis_conn = redis_pool.get()?;
+        util::fetch_rank(user_id, &mut redis_conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
+
+async fn get_around(
+    user_id: Path<i32>,
+    query: web::Query<AroundQuery>,
+    pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+) -> Result<impl Responder> {
+    let user_id = user_id.into_inner();
+    let range = query.range.unwrap_or(LEADERBOARD_AROUND_RANGE);
+    if !(0..=LEADERBOARD_TOP_LIMIT).contains(&range) {
+        return Err(ErrorBadRequest("Invalid query params"));
+    }
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        let mut redis_conn = redis_pool.get()?;
+        util::fetch_around(user_id, range, &mut conn, &mut redis_conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    match response {
+        Some(response) => Ok(Json(response)),
+        None => Err(ErrorNotFound("User is not on the leaderboard")),
+    }
+}
+
+async fn get_metric_leaderboard(
+    metric: Path<LeaderboardMetric>,
+    query: web::Query<MetricLeaderboardQuery>,
+    pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+) -> Result<impl Responder> {
+    let metric = metric.into_inner();
+    let period = query.period.unwrap_or_default();
+    let page = query.page.unwrap_or(1);
+    let limit = query.limit.unwrap_or(20);
+    if page <= 0 || limit <= 0 || limit > LEADERBOARD_TOP_LIMIT {
+        return Err(ErrorBadRequest("Invalid query params"));
+    }
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        let mut redis_conn = redis_pool.get()?;
+        metrics::get_metric_leaderboard(metric, period, page, limit, &mut conn, &mut redis_conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
@@ -0,0 +1,249 @@
+use crate::api::game::util::LeaderboardEntry;
+use crate::api::RedisConn;
+use crate::constants::{LEADERBOARD_KEY, LEADERBOARD_REBUILD_KEY};
+use crate::error::DieselError;
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use redis::Commands;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+pub struct RankResponse {
+    pub user_id: i32,
+    pub rank: Option<i64>,
+    pub trophies: Option<i32>,
+    pub total_players: i64,
+}
+
+#[derive(Serialize)]
+pub struct LeaderboardWindowResponse {
+    pub leaderboard_entries: Vec<LeaderboardEntry>,
+}
+
+// Sets the trophies of the given (user_id, trophies) pairs, adding users that aren't ranked yet
+pub fn update_leaderboard(entries: &[(i32, i32)], redis_conn: &mut RedisConn) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let members: Vec<(i32, i32)> = entries
+        .iter()
+        .map(|(user_id, trophies)| (*trophies, *user_id))
+        .collect();
+    redis_conn.zadd_multiple::<_, _, _, ()>(LEADERBOARD_KEY, &members)?;
+    Ok(())
+}
+
+// Adds the user only if they aren't ranked yet, so it is safe to call on every login
+pub fn join_leaderboard(user_id: i32, trophies: i32, redis_conn: &mut RedisConn) -> Result<()> {
+    redis::cmd("ZADD")
+        .arg(LEADERBOARD_KEY)
+        .arg("NX")
+        .arg(trophies)
+        .arg(user_id)
+        .query::<()>(&mut **redis_conn)?;
+    Ok(())
+}
+
+// 1-based position of the user, None if the user isn't ranked
+pub fn get_rank(user_id: i32, redis_conn: &mut RedisConn) -> Result<Option<i64>> {
+    let rank: Option<i64> = redis_conn.zrevrank(LEADERBOARD_KEY, user_id)?;
+    Ok(rank.map(|rank| rank + 1))
+}
+
+// 1-based position of the user among the players with at most max_trophies
+pub fn get_rank_below(
+    user_id: i32,
+    max_trophies: Option<i32>,
+    redis_conn: &mut RedisConn,
+) -> Result<Option<i64>> {
+    let rank = match get_rank(user_id, redis_conn)? {
+        Some(rank) => rank,
+        None => return Ok(None),
+    };
+    let players_above: i64 = match max_trophies {
+        Some(max_trophies) => {
+            redis_conn.zcount(LEADERBOARD_KEY, format!("({}", max_trophies), "+inf")?
+        }
+        None => 0,
+    };
+    Ok(Some(rank - players_above))
+}
+
+pub fn get_trophies(user_id: i32, redis_conn: &mut RedisConn) -> Result<Option<i32>> {
+    let trophies: Option<i32> = redis_conn.zscore(LEADERBOARD_KEY, user_id)?;
+    Ok(trophies)
+}
+
+pub fn count_players(redis_conn: &mut RedisConn) -> Result<i64> {
+    let total_players: i64 = redis_conn.zcard(LEADERBOARD_KEY)?;
+    Ok(total_players)
+}
+
+pub fn count_players_in_range(
+    min_trophies: i32,
+    max_trophies: i32,
+    redis_conn: &mut RedisConn,
+) -> Result<i64> {
+    let total_players: i64 = redis_conn.zcount(LEADERBOARD_KEY, min_trophies, max_trophies)?;
+    Ok(total_players)
+}
+
+// (user_id, trophies) pairs in descending order of trophies, skipping offset players in the range
+pub fn get_players_in_range(
+    min_trophies: i32,
+    max_trophies: i32,
+    offset: i64,
+    count: i64,
+    redis_conn: &mut RedisConn,
+) -> Result<Vec<(i32, i32)>> {
+    let players: Vec<(i32, i32)> = redis_conn.zrevrangebyscore_limit_withscores(
+        LEADERBOARD_KEY,
+        max_trophies,
+        min_trophies,
+        offset as isize,
+        count as isize,
+    )?;
+    Ok(players)
+}
+
+// (user_id, trophies) pairs between the 0-based start and stop positions, both inclusive
+pub fn get_players_by_position(
+    start: i64,
+    stop: i64,
+    redis_conn: &mut RedisConn,
+) -> Result<Vec<(i32, i32)>> {
+    let players: Vec<(i32, i32)> =
+        redis_conn.zrevrange_withscores(LEADERBOARD_KEY, start as isize, stop as isize)?;
+    Ok(players)
+}
+
+// Fills in the player details of a slice of the leaderboard whose first player is at first_rank
+pub fn fetch_leaderboard_entries(
+    players: &[(i32, i32)],
+    first_rank: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<LeaderboardEntry>> {
+    use crate::schema::user;
+
+    let user_ids: Vec<i32> = players.iter().map(|(user_id, _)| *user_id).collect();
+    let mut details: HashMap<i32, LeaderboardEntry> = user::table
+        .filter(user::id.eq_any(user_ids))
+        .select((
+            user::id,
+            user::username,
+            user::trophies,
+            user::artifacts,
+            user::attacks_won,
+            user::defenses_won,
+            user::avatar_id,
+        ))
+        .load::<(i32, String, i32, i32, i32, i32, i32)>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .map(
+            |(id, name, trophies, artifacts, attacks_won, defenses_won, avatar_id)| {
+                let entry = LeaderboardEntry {
+                    user_id: id,
+                    rank: 0,
+                    name,
+                    trophies,
+                    artifacts,
+                    attacks_won,
+                    defenses_won,
+                    avatar_url: avatar_id,
+                };
+                (id, entry)
+            },
+        )
+        .collect();
+
+    //keep the order of the sorted set, dropping players deleted since it was last updated
+    let entries = players
+        .iter()
+        .zip(first_rank..)
+        .filter_map(|((user_id, _), rank)| {
+            details
+                .remove(user_id)
+                .map(|entry| LeaderboardEntry { rank, ..entry })
+        })
+        .collect();
+    Ok(entries)
+}
+
+pub fn fetch_rank(user_id: i32, redis_conn: &mut RedisConn) -> Result<RankResponse> {
+    Ok(RankResponse {
+        user_id,
+        rank: get_rank(user_id, redis_conn)?,
+        trophies: get_trophies(user_id, redis_conn)?,
+        total_players: count_players(redis_conn)?,
+    })
+}
+
+pub fn fetch_top(
+    limit: i64,
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+) -> Result<LeaderboardWindowResponse> {
+    let players = get_players_by_position(0, limit - 1, redis_conn)?;
+    Ok(LeaderboardWindowResponse {
+        leaderboard_entries: fetch_leaderboard_entries(&players, 1, conn)?,
+    })
+}
+
+// The players ranked within range places above and below the user
+pub fn fetch_around(
+    user_id: i32,
+    range: i64,
+    conn: &mut PgConnection,
+    redis_conn: &mut RedisConn,
+) -> Result<Option<LeaderboardWindowResponse>> {
+    let position = match get_rank(user_id, redis_conn)? {
+        Some(rank) => rank - 1,
+        None => return Ok(None),
+    };
+    let start = (position - range).max(0);
+    let players = get_players_by_position(start, position + range, redis_conn)?;
+    Ok(Some(LeaderboardWindowResponse {
+        leaderboard_entries: fetch_leaderboard_entries(&players, start + 1, conn)?,
+    }))
+}
+
+// Replaces the sorted set with the trophies in Postgres, returning the number of players ranked
+pub fn rebuild_leaderboard(conn: &mut PgConnection, redis_conn: &mut RedisConn) -> Result<usize> {
+    use crate::schema::user;
+
+    let members: Vec<(i32, i32)> = user::table
+        .filter(user::is_pragyan.eq(false))
+        .select((user::trophies, user::id))
+        .load::<(i32, i32)>(conn)
+        .map_err(|err| DieselError {
+            table: "user",
+            function: function!(),
+            error: err,
+        })?;
+
+    if members.is_empty() {
+        redis_conn.del::<_, ()>(LEADERBOARD_KEY)?;
+        return Ok(0);
+    }
+
+    //build under a separate key and swap it in, so readers never see a partial leaderboard
+    redis::pipe()
+        .atomic()
+        .del(LEADERBOARD_REBUILD_KEY)
+        .ignore()
+        .zadd_multiple(LEADERBOARD_REBUILD_KEY, &members)
+        .ignore()
+        .rename(LEADERBOARD_REBUILD_KEY, LEADERBOARD_KEY)
+        .ignore()
+        .query::<()>(&mut **redis_conn)?;
+
+    Ok(members.len())
+}
@@ -0,0 +1,20 @@
+use super::{error, PgPool};
+use actix_web::web::{self, Data, Json};
+use actix_web::{Responder, Result};
+
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(list_leagues)));
+}
+
+async fn list_leagues(pool: Data<PgPool>) -> Result<impl Responder> {
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        Ok(util::league_ranges(&util::fetch_leagues(&mut conn)?))
+            as anyhow::Result<Vec<util::LeagueResponse>>
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
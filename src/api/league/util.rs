@@ -0,0 +1,68 @@
+use crate::error::DieselError;
+use crate::models::League;
+use crate::schema::league;
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LeagueResponse {
+    pub id: i32,
+    pub name: String,
+    pub division: i32,
+    pub min_trophies: i32,
+    pub max_trophies: Option<i32>,
+}
+
+pub fn fetch_leagues(conn: &mut PgConnection) -> Result<Vec<League>> {
+    let leagues = league::table
+        .order_by(league::min_trophies.asc())
+        .load::<League>(conn)
+        .map_err(|err| DieselError {
+            table: "league",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(leagues)
+}
+
+// Leagues sorted by min_trophies, with the upper bound each one ends at
+pub fn league_ranges(leagues: &[League]) -> Vec<LeagueResponse> {
+    leagues
+        .iter()
+        .enumerate()
+        .map(|(index, league)| LeagueResponse {
+            id: league.id,
+            name: league.name.clone(),
+            division: league.division,
+            min_trophies: league.min_trophies,
+            max_trophies: leagues
+                .get(index + 1)
+                .map(|next_league| next_league.min_trophies - 1),
+        })
+        .collect()
+}
+
+// Leagues are derived from trophies, so crossing a threshold promotes or demotes right away
+pub fn league_for_trophies(trophies: i32, leagues: &[LeagueResponse]) -> Option<&LeagueResponse> {
+    leagues
+        .iter()
+        .rev()
+        .find(|league| trophies >= league.min_trophies)
+        .or_else(|| leagues.first())
+}
+
+pub fn fetch_league_for_trophies(
+    trophies: i32,
+    conn: &mut PgConnection,
+) -> Result<Option<LeagueResponse>> {
+    let leagues = league_ranges(&fetch_leagues(conn)?);
+    Ok(league_for_trophies(trophies, &leagues).cloned())
+}
+
+pub fn fetch_league(league_id: i32, conn: &mut PgConnection) -> Result<Option<LeagueResponse>> {
+    let leagues = league_ranges(&fetch_leagues(conn)?);
+    Ok(leagues.into_iter().find(|league| league.id == league_id))
+}
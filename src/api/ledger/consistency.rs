@@ -0,0 +1,383 @@
+use super::util::{move_artifacts, ArtifactAccount};
+use crate::api::util::get_current_levels_fixture;
+use crate::constants::BANK_BUILDING_NAME;
+use crate::error::DieselError;
+use crate::models::{ArtifactLedgerReason, BlockCategory};
+use crate::schema::{artifact, block_type, building_type, map_layout, map_spaces, user};
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    // No base for the current level, nothing else can be checked
+    MissingBase,
+    MissingBank {
+        map_id: i32,
+    },
+    NegativeCount {
+        map_space_id: i32,
+        count: i32,
+    },
+    OverCapacity {
+        map_space_id: i32,
+        building: String,
+        count: i32,
+        capacity: i32,
+    },
+    BalanceMismatch {
+        user_artifacts: i32,
+        base_artifacts: i64,
+    },
+}
+
+#[derive(Serialize)]
+pub struct UserViolation {
+    pub user_id: i32,
+    #[serde(flatten)]
+    pub violation: Violation,
+}
+
+// A count changed by a repair, map_space_id is None for the user's balance
+#[derive(Serialize)]
+pub struct Repair {
+    pub user_id: i32,
+    pub map_space_id: Option<i32>,
+    pub before: i64,
+    pub after: i64,
+}
+
+#[derive(Serialize)]
+pub struct ConsistencyReport {
+    pub level_id: i32,
+    pub users_checked: usize,
+    pub violations: Vec<UserViolation>,
+    pub repairs: Vec<Repair>,
+    pub unrepaired: usize,
+}
+
+struct Space {
+    map_space_id: i32,
+    building: String,
+    capacity: i32,
+    count: i32,
+    is_bank: bool,
+}
+
+struct Base {
+    map_id: i32,
+    spaces: Vec<Space>,
+}
+
+// The base of every player for the given level, keyed by player
+fn fetch_bases(level_id: i32, conn: &mut PgConnection) -> Result<HashMap<i32, Base>> {
+    let mut bases: HashMap<i32, Base> = map_layout::table
+        .filter(map_layout::level_id.eq(level_id))
+        .order_by(map_layout::id.asc())
+        .select((map_layout::player, map_layout::id))
+        .load::<(i32, i32)>(conn)
+        .map_err(|err| DieselError {
+            table: "map_layout",
+            function: function!(),
+            error: err,
+        })?
+        .into_iter()
+        .map(|(player, map_id)| {
+            let base = Base {
+                map_id,
+                spaces: Vec::new(),
+            };
+            (player, base)
+        })
+        .collect();
+
+    let players: HashMap<i32, i32> = bases
+        .iter()
+        .map(|(player, base)| (base.map_id, *player))
+        .collect();
+    let map_ids: Vec<i32> = players.keys().copied().collect();
+    let spaces = map_spaces::table
+        .inner_join(block_type::table.inner_join(building_type::table))
+        .left_join(artifact::table)
+        .filter(map_spaces::map_id.eq_any(map_ids))
+        .select((
+            map_spaces::map_id,
+            map_spaces::id,
+            block_type::category,
+            building_type::name,
+            building_type::capacity,
+            artifact::count.nullable(),
+        ))
+        .load::<(i32, i32, BlockCategory, String, i32, Option<i32>)>(conn)
+        .map_err(|err| DieselError {
+            table: "map_spaces",
+            function: function!(),
+            error: err,
+        })?;
+
+    for (map_id, map_space_id, category, building, capacity, count) in spaces {
+        let is_bank = category == BlockCategory::Building && building == BANK_BUILDING_NAME;
+        if let Some(base) = players
+            .get(&map_id)
+            .and_then(|player| bases.get_mut(player))
+        {
+            base.spaces.push(Space {
+                map_space_id,
+                building,
+                capacity,
+                count: count.unwrap_or(0),
+                is_bank,
+            });
+        }
+    }
+    Ok(bases)
+}
+
+fn check_user(user_artifacts: i32, base: Option<&Base>) -> Vec<Violation> {
+    let base = match base {
+        Some(base) => base,
+        None => return vec![Violation::MissingBase],
+    };
+
+    let mut violations = Vec::new();
+    if !base.spaces.iter().any(|space| space.is_bank) {
+        violations.push(Violation::MissingBank {
+            map_id: base.map_id,
+        });
+    }
+    for space in &base.spaces {
+        if space.count < 0 {
+            violations.push(Violation::NegativeCount {
+                map_space_id: space.map_space_id,
+                count: space.count,
+            });
+        }
+        if space.count > space.capacity {
+            violations.push(Violation::OverCapacity {
+                map_space_id: space.map_space_id,
+                building: space.building.clone(),
+                count: space.count,
+                capacity: space.capacity,
+            });
+        }
+    }
+
+    let base_artifacts: i64 = base.spaces.iter().map(|space| space.count as i64).sum();
+    if base_artifacts != user_artifacts as i64 {
+        violations.push(Violation::BalanceMismatch {
+            user_artifacts,
+            base_artifacts,
+        });
+    }
+    violations
+}
+
+// Repairs what can be repaired through the ledger. Negative counts are raised to zero, artifacts
+// over capacity go to the bank while it has room and are removed otherwise, and the balance is
+// set to the sum of the base. Returns the number of violations left as they are
+fn repair_user(
+    user_id: i32,
+    user_artifacts: i32,
+    base: &mut Base,
+    violations: &[Violation],
+    repairs: &mut Vec<Repair>,
+    conn: &mut PgConnection,
+) -> Result<usize> {
+    let mut unrepaired = 0;
+    let mut balance = user_artifacts as i64;
+    let record = |map_space_id: Option<i32>, before: i32, after: i32| Repair {
+        user_id,
+        map_space_id,
+        before: before as i64,
+        after: after as i64,
+    };
+
+    for violation in violations {
+        match violation {
+            Violation::NegativeCount { map_space_id, .. } => {
+                let space = match base
+                    .spaces
+                    .iter_mut()
+                    .find(|space| space.map_space_id == *map_space_id)
+                {
+                    Some(space) => space,
+                    None => continue,
+                };
+                move_artifacts(
+                    ArtifactAccount::System,
+                    ArtifactAccount::Building {
+                        user_id,
+                        map_space_id: *map_space_id,
+                    },
+                    -space.count,
+                    ArtifactLedgerReason::Adjustment,
+                    None,
+                    conn,
+                )?;
+                repairs.push(record(Some(*map_space_id), space.count, 0));
+                balance -= space.count as i64;
+                space.count = 0;
+            }
+            Violation::MissingBase | Violation::MissingBank { .. } => unrepaired += 1,
+            _ => {}
+        }
+    }
+
+    //buildings other than the bank first, so their excess can still move into it
+    let mut over_capacity: Vec<i32> = violations
+        .iter()
+        .filter_map(|violation| match violation {
+            Violation::OverCapacity { map_space_id, .. } => Some(*map_space_id),
+            _ => None,
+        })
+        .collect();
+    over_capacity.sort_by_key(|map_space_id| {
+        base.spaces
+            .iter()
+            .any(|space| space.map_space_id == *map_space_id && space.is_bank)
+    });
+    for map_space_id in over_capacity {
+        let index = match base
+            .spaces
+            .iter()
+            .position(|space| space.map_space_id == map_space_id)
+        {
+            Some(index) => index,
+            None => continue,
+        };
+        let excess = base.spaces[index].count - base.spaces[index].capacity;
+        if excess <= 0 {
+            continue;
+        }
+        let building = ArtifactAccount::Building {
+            user_id,
+            map_space_id,
+        };
+
+        //move what fits into the bank and remove the rest
+        let mut removed = excess;
+        let bank_index = base
+            .spaces
+            .iter()
+            .position(|space| space.is_bank)
+            .filter(|bank_index| *bank_index != index);
+        if let Some(bank_index) = bank_index {
+            let bank = &mut base.spaces[bank_index];
+            let to_bank = excess.min(bank.capacity - bank.count).max(0);
+            if to_bank > 0 {
+                move_artifacts(
+                    building,
+                    ArtifactAccount::Building {
+                        user_id,
+                        map_space_id: bank.map_space_id,
+                    },
+                    to_bank,
+                    ArtifactLedgerReason::Transfer,
+                    None,
+                    conn,
+                )?;
+                repairs.push(record(
+                    Some(bank.map_space_id),
+                    bank.count,
+                    bank.count + to_bank,
+                ));
+                bank.count += to_bank;
+                removed -= to_bank;
+            }
+        }
+
+        move_artifacts(
+            building,
+            ArtifactAccount::System,
+            removed,
+            ArtifactLedgerReason::Adjustment,
+            None,
+            conn,
+        )?;
+        balance -= removed as i64;
+
+        let space = &mut base.spaces[index];
+        repairs.push(record(Some(map_space_id), space.count, space.capacity));
+        space.count = space.capacity;
+    }
+
+    //the repairs above keep the balance and the base in step, so any mismatch is still there
+    if violations
+        .iter()
+        .any(|violation| matches!(violation, Violation::BalanceMismatch { .. }))
+    {
+        let base_artifacts: i64 = base.spaces.iter().map(|space| space.count as i64).sum();
+        move_artifacts(
+            ArtifactAccount::System,
+            ArtifactAccount::User(user_id),
+            (base_artifacts - balance) as i32,
+            ArtifactLedgerReason::Adjustment,
+            None,
+            conn,
+        )?;
+        repairs.push(Repair {
+            user_id,
+            map_space_id: None,
+            before: balance,
+            after: base_artifacts,
+        });
+    }
+
+    Ok(unrepaired)
+}
+
+// Checks the artifacts of every user against their base for the current level, repairing them in a
+// single transaction if repair is set
+pub fn check_artifacts(repair: bool, conn: &mut PgConnection) -> Result<ConsistencyReport> {
+    conn.transaction(|conn| {
+        let level_id = get_current_levels_fixture(conn)?.id;
+        let users: Vec<(i32, i32)> = user::table
+            .order_by(user::id.asc())
+            .select((user::id, user::artifacts))
+            .load::<(i32, i32)>(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+        let mut bases = fetch_bases(level_id, conn)?;
+
+        let mut violations = Vec::new();
+        let mut repairs = Vec::new();
+        let mut unrepaired = 0;
+        for (user_id, user_artifacts) in &users {
+            let user_violations = check_user(*user_artifacts, bases.get(user_id));
+            if user_violations.is_empty() {
+                continue;
+            }
+
+            unrepaired += match (repair, bases.get_mut(user_id)) {
+                (true, Some(base)) => repair_user(
+                    *user_id,
+                    *user_artifacts,
+                    base,
+                    &user_violations,
+                    &mut repairs,
+                    conn,
+                )?,
+                _ => user_violations.len(),
+            };
+            violations.extend(user_violations.into_iter().map(|violation| UserViolation {
+                user_id: *user_id,
+                violation,
+            }));
+        }
+
+        Ok(ConsistencyReport {
+            level_id,
+            users_checked: users.len(),
+            violations,
+            repairs,
+            unrepaired,
+        })
+    })
+}
@@ -0,0 +1,33 @@
+use super::auth::session::AuthUser;
+use super::{error, PgPool};
+use crate::api::util::HistoryboardQuery;
+use actix_web::error::ErrorBadRequest;
+use actix_web::web::{self, Data, Json};
+use actix_web::{Responder, Result};
+
+pub mod consistency;
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(get_ledger)));
+}
+
+async fn get_ledger(
+    pool: Data<PgPool>,
+    user: AuthUser,
+    query: web::Query<HistoryboardQuery>,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let page = query.page.unwrap_or(1);
+    let limit = query.limit.unwrap_or(20);
+    if page <= 0 || limit <= 0 {
+        return Err(ErrorBadRequest("Invalid query params"));
+    }
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_ledger(user_id, page, limit, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
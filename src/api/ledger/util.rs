@@ -0,0 +1,221 @@
+use crate::error::DieselError;
+use crate::models::{
+    ArtifactLedgerEntry, ArtifactLedgerReason, NewArtifact, NewArtifactLedgerEntry,
+};
+use crate::schema::{artifact, artifact_ledger, user};
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub enum ArtifactAccount {
+    // Where artifacts are minted from and spent into
+    System,
+    // A user's balance alone, for corrections that don't touch any building
+    User(i32),
+    // Artifacts stored in one of the user's buildings
+    Building { user_id: i32, map_space_id: i32 },
+}
+
+impl ArtifactAccount {
+    fn user_id(&self) -> Option<i32> {
+        match self {
+            ArtifactAccount::System => None,
+            ArtifactAccount::User(user_id) => Some(*user_id),
+            ArtifactAccount::Building { user_id, .. } => Some(*user_id),
+        }
+    }
+
+    fn map_space_id(&self) -> Option<i32> {
+        match self {
+            ArtifactAccount::Building { map_space_id, .. } => Some(*map_space_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LedgerResponse {
+    pub balance: i64,
+    pub entries: Vec<ArtifactLedgerEntry>,
+    pub last_page: i64,
+}
+
+// Records a movement without applying it, for balances that were set when the rows were created
+pub fn add_ledger_entry(
+    from: ArtifactAccount,
+    to: ArtifactAccount,
+    amount: i32,
+    reason: ArtifactLedgerReason,
+    reference_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    if amount <= 0 {
+        return Ok(());
+    }
+    diesel::insert_into(artifact_ledger::table)
+        .values(NewArtifactLedgerEntry {
+            from_user_id: from.user_id(),
+            from_map_space_id: from.map_space_id(),
+            to_user_id: to.user_id(),
+            to_map_space_id: to.map_space_id(),
+            amount,
+            reason,
+            reference_id,
+        })
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "artifact_ledger",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
+// Moves artifacts between two accounts, updating user.artifacts and artifact.count alongside the
+// ledger entry. A negative amount moves the other way
+pub fn move_artifacts(
+    from: ArtifactAccount,
+    to: ArtifactAccount,
+    amount: i32,
+    reason: ArtifactLedgerReason,
+    reference_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    if amount < 0 {
+        return move_artifacts(to, from, -amount, reason, reference_id, conn);
+    }
+    if amount == 0 {
+        return Ok(());
+    }
+
+    conn.transaction(|conn| {
+        if let Some(map_space_id) = from.map_space_id() {
+            let updated = diesel::update(
+                artifact::table
+                    .filter(artifact::map_space_id.eq(map_space_id))
+                    .filter(artifact::count.ge(amount)),
+            )
+            .set(artifact::count.eq(artifact::count - amount))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "artifact",
+                function: function!(),
+                error: err,
+            })?;
+            if updated == 0 {
+                return Err(anyhow::anyhow!(
+                    "Not enough artifacts in building:{}",
+                    map_space_id
+                ));
+            }
+        }
+
+        if let Some(user_id) = from.user_id() {
+            diesel::update(user::table.find(user_id))
+                .set(user::artifacts.eq(user::artifacts - amount))
+                .execute(conn)
+                .map_err(|err| DieselError {
+                    table: "user",
+                    function: function!(),
+                    error: err,
+                })?;
+        }
+
+        if let Some(map_space_id) = to.map_space_id() {
+            diesel::insert_into(artifact::table)
+                .values(NewArtifact {
+                    map_space_id,
+                    count: amount,
+                })
+                .on_conflict(artifact::map_space_id)
+                .do_update()
+                .set(artifact::count.eq(artifact::count + amount))
+                .execute(conn)
+                .map_err(|err| DieselError {
+                    table: "artifact",
+                    function: function!(),
+                    error: err,
+                })?;
+        }
+
+        if let Some(user_id) = to.user_id() {
+            diesel::update(user::table.find(user_id))
+                .set(user::artifacts.eq(user::artifacts + amount))
+                .execute(conn)
+                .map_err(|err| DieselError {
+                    table: "user",
+                    function: function!(),
+                    error: err,
+                })?;
+        }
+
+        add_ledger_entry(from, to, amount, reason, reference_id, conn)
+    })
+}
+
+// The user's artifacts as derived from the ledger, which should always equal user.artifacts
+pub fn fetch_ledger_balance(user_id: i32, conn: &mut PgConnection) -> Result<i64> {
+    let received: Option<i64> = artifact_ledger::table
+        .filter(artifact_ledger::to_user_id.eq(user_id))
+        .select(diesel::dsl::sum(artifact_ledger::amount))
+        .first(conn)
+        .map_err(|err| DieselError {
+            table: "artifact_ledger",
+            function: function!(),
+            error: err,
+        })?;
+    let sent: Option<i64> = artifact_ledger::table
+        .filter(artifact_ledger::from_user_id.eq(user_id))
+        .select(diesel::dsl::sum(artifact_ledger::amount))
+        .first(conn)
+        .map_err(|err| DieselError {
+            table: "artifact_ledger",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(received.unwrap_or(0) - sent.unwrap_or(0))
+}
+
+pub fn fetch_ledger(
+    user_id: i32,
+    page: i64,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<LedgerResponse> {
+    let user_entries = artifact_ledger::table.filter(
+        artifact_ledger::from_user_id
+            .eq(user_id)
+            .or(artifact_ledger::to_user_id.eq(user_id)),
+    );
+
+    let total_entries: i64 = user_entries
+        .count()
+        .get_result(conn)
+        .map_err(|err| DieselError {
+            table: "artifact_ledger",
+            function: function!(),
+            error: err,
+        })?;
+    let off_set: i64 = (page - 1) * limit;
+    let last_page: i64 = (total_entries as f64 / limit as f64).ceil() as i64;
+
+    let entries = user_entries
+        .order_by(artifact_ledger::id.desc())
+        .offset(off_set)
+        .limit(limit)
+        .load::<ArtifactLedgerEntry>(conn)
+        .map_err(|err| DieselError {
+            table: "artifact_ledger",
+            function: function!(),
+            error: err,
+        })?;
+
+    Ok(LedgerResponse {
+        balance: fetch_ledger_balance(user_id, conn)?,
+        entries,
+        last_page,
+    })
+}
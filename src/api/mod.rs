@@ -1,9 +1,15 @@
 pub mod attack;
 pub mod auth;
+pub mod challenge;
 pub mod defense;
 pub mod error;
 pub mod game;
 pub mod inventory;
+pub mod leaderboard;
+pub mod league;
+pub mod ledger;
+pub mod notification;
+pub mod season;
 pub mod user;
 pub mod util;
 
@@ -0,0 +1,44 @@
+use super::auth::session::AuthUser;
+use super::{error, PgPool};
+use actix_web::error::ErrorNotFound;
+use actix_web::web::{self, Data, Json, Path};
+use actix_web::{Responder, Result};
+
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(list_notifications)))
+        .service(web::resource("/{notification_id}/read").route(web::post().to(read_notification)));
+}
+
+async fn list_notifications(pool: Data<PgPool>, user: AuthUser) -> Result<impl Responder> {
+    let user_id = user.0;
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_notifications(user_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
+
+async fn read_notification(
+    notification_id: Path<i32>,
+    pool: Data<PgPool>,
+    user: AuthUser,
+) -> Result<impl Responder> {
+    let user_id = user.0;
+    let notification_id = notification_id.into_inner();
+    let is_updated = web::block(move || {
+        let mut conn = pool.get()?;
+        util::mark_notification_read(notification_id, user_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    if !is_updated {
+        return Err(ErrorNotFound("Notification not found"));
+    }
+
+    Ok("Notification marked as read")
+}
@@ -0,0 +1,55 @@
+use crate::error::DieselError;
+use crate::models::{NewNotification, Notification};
+use crate::schema::notification;
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+pub fn add_notification(user_id: i32, message: &str, conn: &mut PgConnection) -> Result<()> {
+    let new_notification = NewNotification {
+        user_id: &user_id,
+        message,
+    };
+
+    diesel::insert_into(notification::table)
+        .values(&new_notification)
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "notification",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
+pub fn fetch_notifications(user_id: i32, conn: &mut PgConnection) -> Result<Vec<Notification>> {
+    let notifications = notification::table
+        .filter(notification::user_id.eq(user_id))
+        .order_by(notification::created_at.desc())
+        .load::<Notification>(conn)
+        .map_err(|err| DieselError {
+            table: "notification",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(notifications)
+}
+
+//marking a notification of the user as read, returns false if the user has no such notification
+pub fn mark_notification_read(
+    notification_id: i32,
+    user_id: i32,
+    conn: &mut PgConnection,
+) -> Result<bool> {
+    let updated = diesel::update(notification::table.find(notification_id))
+        .filter(notification::user_id.eq(user_id))
+        .set(notification::is_read.eq(true))
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "notification",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(updated > 0)
+}
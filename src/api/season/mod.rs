@@ -0,0 +1,20 @@
+use super::{error, PgPool};
+use actix_web::web::{self, Data, Json, Path};
+use actix_web::{Responder, Result};
+
+pub mod util;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{level_id}/results").route(web::get().to(get_season_results)));
+}
+
+async fn get_season_results(level_id: Path<i32>, pool: Data<PgPool>) -> Result<impl Responder> {
+    let level_id = level_id.into_inner();
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_season_results(level_id, &mut conn)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
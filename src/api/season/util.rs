@@ -0,0 +1,159 @@
+use crate::api::inventory::util::{get_bank_map_space_id, get_block_id_of_bank, get_user_map_id};
+use crate::api::ledger::util::{move_artifacts, ArtifactAccount};
+use crate::api::user::util::add_rating_history;
+use crate::constants::{INITIAL_RATING, SEASON_REWARDS};
+use crate::error::DieselError;
+use crate::models::{
+    ArtifactLedgerReason, LevelsFixture, NewRatingHistory, RatingChangeReason, SeasonResult,
+};
+use crate::schema::{levels_fixture, season_result, user};
+use crate::util::function;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+// Trophies pulled towards the initial rating, reset_factor of 0 is a full reset and 1 keeps them
+pub fn soft_reset_trophies(trophies: i32, reset_factor: f32) -> i32 {
+    INITIAL_RATING + ((trophies - INITIAL_RATING) as f32 * reset_factor).round() as i32
+}
+
+pub fn season_reward(final_rank: i32) -> i32 {
+    SEASON_REWARDS
+        .iter()
+        .find(|(lowest_rank, _)| final_rank <= *lowest_rank)
+        .map(|(_, artifacts)| *artifacts)
+        .unwrap_or(0)
+}
+
+pub fn fetch_season_results(level_id: i32, conn: &mut PgConnection) -> Result<Vec<SeasonResult>> {
+    let results = season_result::table
+        .filter(season_result::level_id.eq(level_id))
+        .order_by(season_result::final_rank.asc())
+        .load::<SeasonResult>(conn)
+        .map_err(|err| DieselError {
+            table: "season_result",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(results)
+}
+
+fn grant_artifacts(
+    user_id: i32,
+    artifacts: i32,
+    level_id: i32,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    let map_id = get_user_map_id(user_id, conn)?;
+    let bank_block_type_id = get_block_id_of_bank(conn, &user_id)?;
+    let bank_map_space_id = get_bank_map_space_id(conn, &map_id, &bank_block_type_id)?;
+
+    move_artifacts(
+        ArtifactAccount::System,
+        ArtifactAccount::Building {
+            user_id,
+            map_space_id: bank_map_space_id,
+        },
+        artifacts,
+        ArtifactLedgerReason::SeasonReward,
+        Some(level_id),
+        conn,
+    )
+}
+
+//snapshotting the standings, soft-resetting trophies and granting rewards for a finished season.
+//Returns false if the season was already rolled over
+pub fn end_season(level_id: i32, conn: &mut PgConnection) -> Result<bool> {
+    conn.transaction(|conn| {
+        let season = levels_fixture::table
+            .find(level_id)
+            .for_update()
+            .first::<LevelsFixture>(conn)
+            .map_err(|err| DieselError {
+                table: "levels_fixture",
+                function: function!(),
+                error: err,
+            })?;
+
+        if season.is_rolled_over {
+            return Ok(false);
+        }
+
+        if season.end_date > chrono::Local::now().naive_local() {
+            return Err(anyhow::anyhow!("Season:{} has not ended yet", level_id));
+        }
+
+        let standings = user::table
+            .filter(user::is_pragyan.eq(false))
+            .order_by((user::trophies.desc(), user::id.asc()))
+            .select((user::id, user::trophies))
+            .load::<(i32, i32)>(conn)
+            .map_err(|err| DieselError {
+                table: "user",
+                function: function!(),
+                error: err,
+            })?;
+
+        let results: Vec<SeasonResult> = standings
+            .iter()
+            .enumerate()
+            .map(|(position, &(user_id, trophies))| {
+                let final_rank = position as i32 + 1;
+                SeasonResult {
+                    level_id,
+                    user_id,
+                    final_rank,
+                    final_trophies: trophies,
+                    reset_trophies: soft_reset_trophies(trophies, season.reset_factor),
+                    reward_artifacts: season_reward(final_rank),
+                }
+            })
+            .collect();
+
+        diesel::insert_into(season_result::table)
+            .values(&results)
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "season_result",
+                function: function!(),
+                error: err,
+            })?;
+
+        let mut history = Vec::new();
+        for result in &results {
+            if result.reset_trophies != result.final_trophies {
+                diesel::update(user::table.find(result.user_id))
+                    .set(user::trophies.eq(result.reset_trophies))
+                    .execute(conn)
+                    .map_err(|err| DieselError {
+                        table: "user",
+                        function: function!(),
+                        error: err,
+                    })?;
+                history.push(NewRatingHistory {
+                    user_id: result.user_id,
+                    old_rating: result.final_trophies,
+                    new_rating: result.reset_trophies,
+                    reason: RatingChangeReason::SeasonReset,
+                    game_id: None,
+                });
+            }
+
+            if result.reward_artifacts > 0 {
+                grant_artifacts(result.user_id, result.reward_artifacts, level_id, conn)?;
+            }
+        }
+        add_rating_history(conn, &history)?;
+
+        diesel::update(levels_fixture::table.find(level_id))
+            .set(levels_fixture::is_rolled_over.eq(true))
+            .execute(conn)
+            .map_err(|err| DieselError {
+                table: "levels_fixture",
+                function: function!(),
+                error: err,
+            })?;
+
+        Ok(true)
+    })
+}
@@ -1,6 +1,8 @@
 use super::auth::session::AuthUser;
 use super::{PgPool, RedisPool};
 use crate::api::error;
+use crate::api::league::util::{fetch_leagues, league_ranges};
+use crate::api::util::HistoryboardQuery;
 use crate::models::UpdateUser;
 use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorNotFound};
 use actix_web::web::{self, Data, Json, Path};
@@ -13,7 +15,8 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/update").route(web::patch().to(update_user)))
         .service(web::resource("/profile/{player_id}").route(web::get().to(view_user_profile)))
         .service(web::resource("/register").route(web::post().to(register)))
-        .service(web::resource("/{id}/stats").route(web::get().to(get_user_stats)));
+        .service(web::resource("/{id}/stats").route(web::get().to(get_user_stats)))
+        .service(web::resource("/{id}/rating-history").route(web::get().to(get_rating_history)));
 }
 
 #[derive(Clone, Deserialize)]
@@ -98,7 +101,11 @@ async fn update_user(
     Ok("User updated successfully")
 }
 
-async fn get_user_stats(user_id: Path<i32>, pool: Data<PgPool>) -> Result<impl Responder> {
+async fn get_user_stats(
+    user_id: Path<i32>,
+    pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+) -> Result<impl Responder> {
     let user_id = user_id.into_inner();
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user = web::block(move || util::fetch_user(&mut conn, user_id))
@@ -107,10 +114,10 @@ async fn get_user_stats(user_id: Path<i32>, pool: Data<PgPool>) -> Result<impl R
     if let Some(user) = user {
         let response = web::block(move || {
             let mut conn = pool.get()?;
+            let mut redis_conn = redis_pool.get()?;
             let attack_game = util::fetch_attack_game(&mut conn, user_id)?;
             let defense_game = util::fetch_defense_game(&mut conn, user_id)?;
-            let users = util::fetch_all_user(&mut conn)?;
-            util::make_response(&user, &attack_game, &defense_game, &users)
+            util::make_response(&user, &attack_game, &defense_game, &mut redis_conn)
         })
         .await?
         .map_err(|err| error::handle_error(err.into()))?;
@@ -120,7 +127,31 @@ async fn get_user_stats(user_id: Path<i32>, pool: Data<PgPool>) -> Result<impl R
     }
 }
 
-async fn view_user_profile(player_id: Path<i32>, pool: Data<PgPool>) -> Result<impl Responder> {
+async fn get_rating_history(
+    user_id: Path<i32>,
+    pool: Data<PgPool>,
+    query: web::Query<HistoryboardQuery>,
+) -> Result<impl Responder> {
+    let user_id = user_id.into_inner();
+    let page = query.page.unwrap_or(1);
+    let limit = query.limit.unwrap_or(20);
+    if page <= 0 || limit <= 0 {
+        return Err(ErrorBadRequest("Invalid query params"));
+    }
+    let response = web::block(move || {
+        let mut conn = pool.get()?;
+        util::fetch_rating_history(&mut conn, user_id, page, limit)
+    })
+    .await?
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(response))
+}
+
+async fn view_user_profile(
+    player_id: Path<i32>,
+    pool: Data<PgPool>,
+    redis_pool: Data<RedisPool>,
+) -> Result<impl Responder> {
     let user_id = player_id.into_inner();
     let mut conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user = web::block(move || util::fetch_user(&mut conn, user_id))
@@ -129,8 +160,9 @@ async fn view_user_profile(player_id: Path<i32>, pool: Data<PgPool>) -> Result<i
     if let Some(user) = user {
         let response = web::block(move || {
             let mut conn = pool.get()?;
-            let users = util::fetch_all_user(&mut conn)?;
-            util::make_profile_response(&user, &users)
+            let mut redis_conn = redis_pool.get()?;
+            let leagues = league_ranges(&fetch_leagues(&mut conn)?);
+            util::make_profile_response(&user, &leagues, &mut redis_conn)
         })
         .await?
         .map_err(|err| error::handle_error(err.into()))?;
@@ -1,11 +1,14 @@
 use super::InputUser;
+use crate::api::leaderboard::util::{get_rank, get_rank_below, join_leaderboard};
+use crate::api::league::util::{league_for_trophies, LeagueResponse};
 use crate::api::RedisConn;
 use crate::constants::INITIAL_RATING;
 use crate::error::DieselError;
 use crate::models::NewUser;
-use crate::models::{Game, UpdateUser, User};
+use crate::models::{Game, NewRatingHistory, RatingHistory, UpdateUser, User};
 use crate::util::function;
 use anyhow::Result;
+use chrono::{Local, NaiveDateTime};
 use diesel::prelude::*;
 use redis::Commands;
 use serde::Serialize;
@@ -35,6 +38,9 @@ pub struct UserProfileResponse {
     defenses_won: i32,
     avatar_id: i32,
     leaderboard_position: i32,
+    shield_until: Option<NaiveDateTime>,
+    league: Option<LeagueResponse>,
+    league_position: i32,
 }
 
 pub fn fetch_user(conn: &mut PgConnection, player_id: i32) -> Result<Option<User>> {
@@ -50,18 +56,6 @@ pub fn fetch_user(conn: &mut PgConnection, player_id: i32) -> Result<Option<User
         })?)
 }
 
-pub fn fetch_all_user(conn: &mut PgConnection) -> Result<Vec<User>> {
-    use crate::schema::user;
-    Ok(user::table
-        .order_by(user::trophies.desc())
-        .load::<User>(conn)
-        .map_err(|err| DieselError {
-            table: "user",
-            function: function!(),
-            error: err,
-        })?)
-}
-
 pub fn add_user(
     pg_conn: &mut PgConnection,
     mut redis_conn: RedisConn,
@@ -89,6 +83,7 @@ pub fn add_user(
         })?;
     // Set last reset password time as 0 for new user
     redis_conn.set(user.id, 0)?;
+    join_leaderboard(user.id, user.trophies, &mut redis_conn)?;
     Ok(())
 }
 
@@ -131,10 +126,68 @@ pub fn get_duplicate_username(conn: &mut PgConnection, username: &str) -> Result
         })?)
 }
 
+#[derive(Serialize)]
+pub struct RatingHistoryResponse {
+    pub history: Vec<RatingHistory>,
+    pub last_page: i64,
+}
+
+pub fn add_rating_history(conn: &mut PgConnection, entries: &[NewRatingHistory]) -> Result<()> {
+    use crate::schema::rating_history;
+    if entries.is_empty() {
+        return Ok(());
+    }
+    diesel::insert_into(rating_history::table)
+        .values(entries)
+        .execute(conn)
+        .map_err(|err| DieselError {
+            table: "rating_history",
+            function: function!(),
+            error: err,
+        })?;
+    Ok(())
+}
+
+pub fn fetch_rating_history(
+    conn: &mut PgConnection,
+    user_id: i32,
+    page: i64,
+    limit: i64,
+) -> Result<RatingHistoryResponse> {
+    use crate::schema::rating_history;
+
+    let total_entries: i64 = rating_history::table
+        .filter(rating_history::user_id.eq(user_id))
+        .count()
+        .get_result(conn)
+        .map_err(|err| DieselError {
+            table: "rating_history",
+            function: function!(),
+            error: err,
+        })?;
+    let off_set: i64 = (page - 1) * limit;
+    let last_page: i64 = (total_entries as f64 / limit as f64).ceil() as i64;
+
+    let history = rating_history::table
+        .filter(rating_history::user_id.eq(user_id))
+        .order_by((rating_history::created_at.desc(), rating_history::id.desc()))
+        .offset(off_set)
+        .limit(limit)
+        .load::<RatingHistory>(conn)
+        .map_err(|err| DieselError {
+            table: "rating_history",
+            function: function!(),
+            error: err,
+        })?;
+
+    Ok(RatingHistoryResponse { history, last_page })
+}
+
 pub fn fetch_attack_game(conn: &mut PgConnection, player_id: i32) -> Result<Vec<Game>> {
     use crate::schema::game;
     Ok(game::table
         .filter(game::attack_id.eq(player_id))
+        .filter(game::is_practice.eq(false))
         .order_by(game::attack_score.desc())
         .load::<Game>(conn)
         .map_err(|err| DieselError {
@@ -148,6 +201,7 @@ pub fn fetch_defense_game(conn: &mut PgConnection, player_id: i32) -> Result<Vec
     use crate::schema::game;
     Ok(game::table
         .filter(game::defend_id.eq(player_id))
+        .filter(game::is_practice.eq(false))
         .order_by(game::defend_score.desc())
         .load::<Game>(conn)
         .map_err(|err| DieselError {
@@ -157,8 +211,18 @@ pub fn fetch_defense_game(conn: &mut PgConnection, player_id: i32) -> Result<Vec
         })?)
 }
 
-pub fn make_profile_response(user: &User, users: &[User]) -> Result<UserProfileResponse> {
-    let mut profile = UserProfileResponse {
+pub fn make_profile_response(
+    user: &User,
+    leagues: &[LeagueResponse],
+    redis_conn: &mut RedisConn,
+) -> Result<UserProfileResponse> {
+    let league = league_for_trophies(user.trophies, leagues).cloned();
+    let league_position = match &league {
+        Some(league) => get_rank_below(user.id, league.max_trophies, redis_conn)?.unwrap_or(0),
+        None => 0,
+    };
+    let leaderboard_position = get_rank(user.id, redis_conn)?.unwrap_or(0);
+    Ok(UserProfileResponse {
         user_id: user.id,
         name: user.name.clone(),
         username: user.username.clone(),
@@ -167,31 +231,26 @@ pub fn make_profile_response(user: &User, users: &[User]) -> Result<UserProfileR
         attacks_won: user.attacks_won,
         defenses_won: user.defenses_won,
         avatar_id: user.avatar_id,
-        leaderboard_position: 0,
-    };
-    if !users.is_empty() {
-        for (i, u) in users.iter().enumerate() {
-            if user.id == u.id {
-                profile.leaderboard_position = i as i32;
-                break;
-            }
-        }
-        profile.leaderboard_position += 1;
-    }
-    Ok(profile)
+        leaderboard_position: leaderboard_position as i32,
+        shield_until: user
+            .shield_until
+            .filter(|shield_until| *shield_until > Local::now().naive_local()),
+        league,
+        league_position: league_position as i32,
+    })
 }
 
 pub fn make_response(
     user: &User,
     attack_game: &[Game],
     defense_game: &[Game],
-    users: &[User],
+    redis_conn: &mut RedisConn,
 ) -> Result<StatsResponse> {
     let mut stats = StatsResponse {
         highest_attack_score: 0,
         highest_defense_score: 0,
         trophies: user.trophies,
-        position_in_leaderboard: 0,
+        position_in_leaderboard: get_rank(user.id, redis_conn)?.unwrap_or(0) as i32,
         no_of_emps_used: 0,
         total_damage_defense: 0,
         total_damage_attack: 0,
@@ -216,14 +275,5 @@ pub fn make_response(
             stats.total_damage_defense += defend.damage_done;
         }
     }
-    if !users.is_empty() {
-        for (i, u) in users.iter().enumerate() {
-            if user.id == u.id {
-                stats.position_in_leaderboard = i as i32;
-                break;
-            }
-        }
-        stats.position_in_leaderboard += 1;
-    }
     Ok(stats)
 }
@@ -28,9 +28,11 @@ pub struct HistoryboardEntry {
     pub damage_percent: i32,
     pub artifacts_taken: i32,
     pub trophies_taken: i32,
+    pub stars: i32,
     pub match_id: i32,
     pub replay_availability: bool,
     pub avatar_id: i32,
+    pub is_revenged: bool,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -0,0 +1,39 @@
+use aot_backend::api::inventory::shop::add_unlockable;
+use aot_backend::api::inventory::upgrade::ItemKind;
+use aot_backend::util;
+
+// Usage: add_unlockable <building|defender|mine|attacker|emp> <item_id> <cost> [min_trophies] [min_bank_level]
+// item_id is a block type id for buildings, defenders and mines
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: add_unlockable <item_type> <item_id> <cost> [min_trophies] [min_bank_level]"
+        );
+        std::process::exit(1);
+    }
+
+    let kind: ItemKind = serde_json::from_value(serde_json::Value::String(args[0].clone()))
+        .expect("Invalid item type");
+    let parse = |index: usize, default: i32| match args.get(index) {
+        Some(arg) => arg.parse::<i32>().expect("Arguments must be integers"),
+        None => default,
+    };
+
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+
+    let unlockable = add_unlockable(
+        kind,
+        parse(1, 0),
+        parse(2, 0),
+        parse(3, 0),
+        parse(4, 1),
+        &mut conn,
+    )
+    .expect("Could not add unlockable");
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&unlockable).expect("Could not serialize unlockable")
+    );
+}
@@ -0,0 +1,20 @@
+use aot_backend::api::ledger::consistency::check_artifacts;
+use aot_backend::util;
+
+// Usage: check_artifacts [--fix]
+fn main() {
+    let repair = std::env::args().any(|arg| arg == "--fix");
+
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+
+    let report = check_artifacts(repair, &mut conn).expect("Could not check artifacts");
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Could not serialize report")
+    );
+
+    if report.unrepaired > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,11 @@
+use aot_backend::api::inventory::builder::resolve_all_upgrades;
+use aot_backend::util;
+
+// Applies every upgrade whose timer has run out, meant to be run periodically
+fn main() {
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+
+    let applied = resolve_all_upgrades(&mut conn).expect("Could not complete upgrades");
+    println!("Completed {} upgrades", applied);
+}
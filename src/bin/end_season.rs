@@ -0,0 +1,33 @@
+use aot_backend::api::leaderboard::util::rebuild_leaderboard;
+use aot_backend::api::season::util::end_season;
+use aot_backend::schema::levels_fixture;
+use aot_backend::util;
+use diesel::prelude::*;
+use std::env;
+
+// Rolls over the given season, or the latest one that has ended if none is given
+fn main() {
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+
+    let level_id = match env::args().nth(1) {
+        Some(level_id) => level_id.parse::<i32>().expect("Invalid level id"),
+        None => levels_fixture::table
+            .filter(levels_fixture::end_date.le(chrono::Local::now().naive_local()))
+            .order_by(levels_fixture::end_date.desc())
+            .select(levels_fixture::id)
+            .first::<i32>(&mut conn)
+            .expect("Could not find an ended season"),
+    };
+
+    if end_season(level_id, &mut conn).expect("Could not end season") {
+        println!("Season {} rolled over", level_id);
+    } else {
+        println!("Season {} was already rolled over", level_id);
+    }
+
+    let mut redis_conn = util::get_redis_conn_pool()
+        .get()
+        .expect("Could not retrieve connection from redis pool");
+    rebuild_leaderboard(&mut conn, &mut redis_conn).expect("Could not rebuild leaderboard");
+}
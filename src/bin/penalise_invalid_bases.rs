@@ -1,5 +1,8 @@
 use aot_backend::api;
+use aot_backend::api::leaderboard::util::rebuild_leaderboard;
+use aot_backend::api::user::util::add_rating_history;
 use aot_backend::constants::SCALE_FACTOR;
+use aot_backend::models::{NewRatingHistory, RatingChangeReason};
 use aot_backend::schema::{map_layout, user};
 use aot_backend::util;
 use diesel::QueryDsl;
@@ -12,21 +15,45 @@ fn main() {
     let level_id = api::util::get_current_levels_fixture(&mut conn)
         .expect("Could not get level id")
         .id;
-    let invalid_users = user::table
-        .left_join(
-            map_layout::table.on(map_layout::player
-                .eq(user::id)
-                .and(map_layout::level_id.eq(level_id))
-                .and(map_layout::is_valid.eq(true))),
-        )
-        .select(user::id)
-        .filter(map_layout::is_valid.is_null())
-        .load::<i32>(&mut conn)
-        .expect("Could not get invalid users");
+    let penalty = (4.0 * SCALE_FACTOR) as i32;
 
-    update(user::table)
-        .filter(user::id.eq_any(invalid_users))
-        .set(user::trophies.eq(user::trophies - ((4.0 * SCALE_FACTOR) as i32)))
-        .execute(&mut conn)
-        .expect("Could not update user ratings");
+    conn.transaction(|conn| {
+        let invalid_users = user::table
+            .left_join(
+                map_layout::table.on(map_layout::player
+                    .eq(user::id)
+                    .and(map_layout::level_id.eq(level_id))
+                    .and(map_layout::is_valid.eq(true))),
+            )
+            .select((user::id, user::trophies))
+            .filter(map_layout::is_valid.is_null())
+            .load::<(i32, i32)>(conn)?;
+
+        let invalid_user_ids: Vec<i32> =
+            invalid_users.iter().map(|(user_id, _)| *user_id).collect();
+        update(user::table)
+            .filter(user::id.eq_any(invalid_user_ids))
+            .set(user::trophies.eq(user::trophies - penalty))
+            .execute(conn)?;
+
+        let history: Vec<NewRatingHistory> = invalid_users
+            .into_iter()
+            .map(|(user_id, old_rating)| NewRatingHistory {
+                user_id,
+                old_rating,
+                new_rating: old_rating - penalty,
+                reason: RatingChangeReason::InvalidBasePenalty,
+                game_id: None,
+            })
+            .collect();
+        add_rating_history(conn, &history)?;
+
+        Ok(()) as anyhow::Result<()>
+    })
+    .expect("Could not update user ratings");
+
+    let mut redis_conn = util::get_redis_conn_pool()
+        .get()
+        .expect("Could not retrieve connection from redis pool");
+    rebuild_leaderboard(&mut conn, &mut redis_conn).expect("Could not rebuild leaderboard");
 }
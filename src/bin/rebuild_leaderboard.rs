@@ -0,0 +1,15 @@
+use aot_backend::api::leaderboard::util::rebuild_leaderboard;
+use aot_backend::util;
+
+// Repopulates the redis leaderboard from the trophies in postgres
+fn main() {
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+    let mut redis_conn = util::get_redis_conn_pool()
+        .get()
+        .expect("Could not retrieve connection from redis pool");
+
+    let ranked =
+        rebuild_leaderboard(&mut conn, &mut redis_conn).expect("Could not rebuild leaderboard");
+    println!("Leaderboard rebuilt with {} players", ranked);
+}
@@ -0,0 +1,103 @@
+use aot_backend::api::attack::rating::{game_scores, rating_system_for, PlayerRating};
+use aot_backend::api::attack::util::max_stars;
+use aot_backend::constants::{
+    INITIAL_RATING, INITIAL_RATING_DEVIATION, INITIAL_RATING_VOLATILITY, SCALE_FACTOR,
+};
+use aot_backend::models::{LevelsFixture, RatingSystemKind};
+use aot_backend::schema::{game, levels_fixture, map_layout, user};
+use aot_backend::util;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::env;
+
+// Replays every finished game under a candidate rating system and compares the result with the
+// current trophies, without writing anything
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let kind = match args.get(1).map(String::as_str) {
+        Some("modified_elo") => RatingSystemKind::ModifiedElo,
+        Some("glicko2") => RatingSystemKind::Glicko2,
+        _ => {
+            eprintln!("Usage: rerate_games <modified_elo|glicko2> [k_factor]");
+            return;
+        }
+    };
+    let k_factor = args
+        .get(2)
+        .map(|k_factor| k_factor.parse::<f32>().expect("Invalid k_factor"))
+        .unwrap_or(SCALE_FACTOR);
+    let rating_system = rating_system_for(kind, k_factor);
+
+    let pool = util::get_pg_conn_pool();
+    let mut conn = pool.get().expect("Could not retrieve connection from pool");
+
+    let games = game::table
+        .inner_join(map_layout::table.inner_join(levels_fixture::table))
+        .filter(game::is_game_over.eq(true))
+        .filter(game::is_practice.eq(false))
+        .filter(game::challenge_id.is_null())
+        .order_by((game::start_time.asc(), game::id.asc()))
+        .select((
+            game::attack_id,
+            game::defend_id,
+            game::damage_done,
+            game::stars,
+            levels_fixture::all_columns,
+        ))
+        .load::<(i32, i32, i32, i32, LevelsFixture)>(&mut conn)
+        .expect("Could not get games");
+
+    let users = user::table
+        .select((user::id, user::username, user::trophies))
+        .load::<(i32, String, i32)>(&mut conn)
+        .expect("Could not get users");
+
+    let initial_rating = PlayerRating {
+        rating: INITIAL_RATING,
+        deviation: INITIAL_RATING_DEVIATION,
+        volatility: INITIAL_RATING_VOLATILITY,
+    };
+    let mut ratings: HashMap<i32, PlayerRating> = HashMap::new();
+    let mut rated_games = 0;
+
+    for (attacker_id, defender_id, damage_done, stars, levels_fixture) in games {
+        let attacker = *ratings.get(&attacker_id).unwrap_or(&initial_rating);
+        let defender = *ratings.get(&defender_id).unwrap_or(&initial_rating);
+        let (attack_score, defence_score) =
+            game_scores(damage_done, stars, max_stars(&levels_fixture));
+        let (new_attacker, new_defender) =
+            rating_system.rate(&attacker, &defender, attack_score, defence_score);
+
+        ratings.insert(attacker_id, new_attacker);
+        ratings.insert(defender_id, new_defender);
+        rated_games += 1;
+    }
+
+    println!(
+        "Re-rated {} games with {}",
+        rated_games,
+        rating_system.name()
+    );
+    println!("user_id,username,current_trophies,rerated_trophies,difference");
+
+    let mut total_difference = 0;
+    for (user_id, username, trophies) in &users {
+        let rerated = ratings.get(user_id).unwrap_or(&initial_rating).rating;
+        total_difference += (rerated - trophies).abs();
+        println!(
+            "{},{},{},{},{}",
+            user_id,
+            username,
+            trophies,
+            rerated,
+            rerated - trophies
+        );
+    }
+
+    if !users.is_empty() {
+        println!(
+            "Mean absolute difference: {:.2}",
+            total_difference as f32 / users.len() as f32
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use aot_backend::api::leaderboard::util::rebuild_leaderboard;
+use aot_backend::api::user::util::add_rating_history;
+use aot_backend::constants::INITIAL_RATING;
+use aot_backend::models::{NewRatingHistory, RatingChangeReason};
 use aot_backend::schema::user;
 use aot_backend::util;
 use diesel::{prelude::*, update};
@@ -6,8 +10,34 @@ fn main() {
     let pool = util::get_pg_conn_pool();
     let mut conn = pool.get().expect("Could not retrieve connection from pool");
 
-    update(user::table)
-        .set(user::trophies.eq(1000))
-        .execute(&mut conn)
-        .expect("Could not update user ratings");
+    conn.transaction(|conn| {
+        let old_ratings = user::table
+            .filter(user::trophies.ne(INITIAL_RATING))
+            .select((user::id, user::trophies))
+            .load::<(i32, i32)>(conn)?;
+
+        update(user::table)
+            .set(user::trophies.eq(INITIAL_RATING))
+            .execute(conn)?;
+
+        let history: Vec<NewRatingHistory> = old_ratings
+            .into_iter()
+            .map(|(user_id, old_rating)| NewRatingHistory {
+                user_id,
+                old_rating,
+                new_rating: INITIAL_RATING,
+                reason: RatingChangeReason::AdminReset,
+                game_id: None,
+            })
+            .collect();
+        add_rating_history(conn, &history)?;
+
+        Ok(()) as anyhow::Result<()>
+    })
+    .expect("Could not update user ratings");
+
+    let mut redis_conn = util::get_redis_conn_pool()
+        .get()
+        .expect("Could not retrieve connection from redis pool");
+    rebuild_leaderboard(&mut conn, &mut redis_conn).expect("Could not rebuild leaderboard");
 }
@@ -4,13 +4,45 @@ pub const ROAD_ID: i32 = 0;
 pub const BANK_BUILDING_NAME: &str = "Bank";
 pub const INITIAL_RATING: i32 = 1000;
 pub const INITIAL_ARTIFACTS: i32 = 1250;
-pub const WIN_THRESHOLD: i32 = 50;
 pub const SCALE_FACTOR: f32 = 20.0;
 pub const HIGHEST_TROPHY: f32 = 2_000.0;
 pub const MAX_BOMBS_PER_ATTACK: i32 = 30;
 pub const ATTACK_TOKEN_AGE_IN_MINUTES: i64 = 5;
 pub const GAME_AGE_IN_MINUTES: usize = 3;
+pub const SHORTEST_PATHS_CACHE_AGE_IN_DAYS: usize = 7;
 pub const MATCH_MAKING_ATTEMPTS: i32 = 10;
+pub const MATCH_MAKING_TROPHY_WINDOW: i32 = 100;
+pub const MATCH_MAKING_TROPHY_WINDOW_STEP: i32 = 100;
+pub const MATCH_MAKING_CANDIDATES_PER_ATTEMPT: i64 = 10;
+pub const RECENT_OPPONENT_COOLDOWN_IN_HOURS: i64 = 6;
+pub const REVENGE_WINDOW_IN_HOURS: i64 = 24;
+pub const SHIELD_DAMAGE_THRESHOLD: i32 = 60;
+pub const SHIELD_MINUTES_PER_DAMAGE: i64 = 6;
+pub const LEADERBOARD_KEY: &str = "leaderboard";
+pub const LEADERBOARD_REBUILD_KEY: &str = "leaderboard:rebuild";
+pub const LEADERBOARD_TOP_LIMIT: i64 = 100;
+pub const LEADERBOARD_AROUND_RANGE: i64 = 5;
+pub const METRIC_LEADERBOARD_CACHE_SECONDS: usize = 300;
+pub const MAX_CHALLENGE_TROPHY_STAKES: i32 = 50;
+pub const INITIAL_RATING_DEVIATION: f32 = 350.0;
+pub const INITIAL_RATING_VOLATILITY: f32 = 0.06;
+pub const GLICKO2_SCALE: f64 = 173.7178;
+pub const GLICKO2_TAU: f64 = 0.5;
+pub const GLICKO2_CONVERGENCE_TOLERANCE: f64 = 0.000001;
+// (lowest final rank that qualifies, artifacts rewarded)
+pub const SEASON_REWARDS: [(i32, i32); 5] = [(1, 1000), (3, 750), (10, 500), (50, 250), (100, 100)];
+// percentage of the destroyed buildings' artifacts kept by the attacker, indexed by stars
+pub const STAR_LOOT_PERCENTAGES: [i32; 4] = [50, 75, 90, 100];
+// building_type.production_rate is the number of artifacts produced in this many seconds
+pub const PRODUCTION_INTERVAL_SECONDS: i64 = 3600;
+// road hops a defender covering a building is worth when auto distributing artifacts
+pub const AUTO_DISTRIBUTION_DEFENDER_WEIGHT: i32 = 5;
+// share of the artifacts spent on an item that come back when it is sold or downgraded
+pub const BUILDING_REFUND_PERCENTAGE: i32 = 50;
+pub const DEFENDER_REFUND_PERCENTAGE: i32 = 50;
+pub const MINE_REFUND_PERCENTAGE: i32 = 50;
+pub const ATTACKER_REFUND_PERCENTAGE: i32 = 40;
+pub const EMP_REFUND_PERCENTAGE: i32 = 40;
 pub const PERCENTANGE_ARTIFACTS_OBTAINABLE: f32 = 0.3;
 pub const BOMB_DAMAGE_MULTIPLIER: f32 = 5.0;
 
@@ -1,4 +1,7 @@
-use crate::api::{attack, auth, defense, game, inventory, user};
+use crate::api::{
+    attack, auth, challenge, defense, game, inventory, leaderboard, league, ledger, notification,
+    season, user,
+};
 use actix_cors::Cors;
 use actix_session::{
     config::PersistentSession, storage::RedisActorSessionStore, SessionMiddleware,
@@ -46,6 +49,11 @@ async fn main() -> std::io::Result<()> {
 
     let conn = &mut pg_pool.get().expect("Could not get connection from pool");
     conn.run_pending_migrations(MIGRATIONS).unwrap();
+    let redis_conn = &mut redis_pool
+        .get()
+        .expect("Could not get connection from pool");
+    leaderboard::util::rebuild_leaderboard(conn, redis_conn)
+        .expect("Could not rebuild leaderboard");
     let max_age: i64 = std::env::var("MAX_AGE_IN_MINUTES")
         .expect("max age must be set!")
         .parse()
@@ -82,8 +90,14 @@ async fn main() -> std::io::Result<()> {
                     .configure(auth::routes),
             )
             .service(web::scope("/base").configure(defense::routes))
+            .service(web::scope("/challenge").configure(challenge::routes))
             .service(web::scope("/game").configure(game::routes))
             .service(web::scope("/inventory").configure(inventory::routes))
+            .service(web::scope("/leaderboard").configure(leaderboard::routes))
+            .service(web::scope("/league").configure(league::routes))
+            .service(web::scope("/ledger").configure(ledger::routes))
+            .service(web::scope("/notification").configure(notification::routes))
+            .service(web::scope("/season").configure(season::routes))
     })
     .bind("0.0.0.0:8000")?
     .run()
@@ -11,6 +11,24 @@ pub enum BlockCategory {
 }
 
 #[derive(diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Copy, Deserialize)]
+#[DieselTypePath = "crate::schema::sql_types::ArtifactLedgerReason"]
+pub enum ArtifactLedgerReason {
+    OpeningBalance,
+    Initial,
+    Loot,
+    Upgrade,
+    Transfer,
+    Grant,
+    SeasonReward,
+    Adjustment,
+    Production,
+    Unlock,
+    Refund,
+}
+
+#[derive(
+    diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Eq, Hash, Copy, Deserialize,
+)]
 #[DieselTypePath = "crate::schema::sql_types::ItemCategory"]
 pub enum ItemCategory {
     Attacker,
@@ -18,6 +36,41 @@ pub enum ItemCategory {
     Block,
 }
 
+#[derive(diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Copy, Deserialize)]
+#[DieselTypePath = "crate::schema::sql_types::MatchmakingMode"]
+pub enum MatchmakingMode {
+    TrophyNearest,
+    BaseStrength,
+    Random,
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Copy, Deserialize)]
+#[DieselTypePath = "crate::schema::sql_types::RatingSystem"]
+pub enum RatingSystemKind {
+    ModifiedElo,
+    Glicko2,
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Copy, Deserialize)]
+#[DieselTypePath = "crate::schema::sql_types::RatingChangeReason"]
+pub enum RatingChangeReason {
+    Game,
+    Challenge,
+    AdminReset,
+    InvalidBasePenalty,
+    SeasonReset,
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Serialize, Clone, PartialEq, Copy, Deserialize)]
+#[DieselTypePath = "crate::schema::sql_types::ChallengeStatus"]
+pub enum ChallengeStatus {
+    Pending,
+    Accepted,
+    InProgress,
+    Declined,
+    Completed,
+}
+
 #[derive(Queryable, Serialize, Clone, Debug)]
 pub struct EmpType {
     pub id: i32,
@@ -74,6 +127,7 @@ pub struct BuildingType {
     pub level: i32,
     pub cost: i32,
     pub hp: i32,
+    pub production_rate: i32,
 }
 
 #[derive(Insertable)]
@@ -100,6 +154,46 @@ pub struct NewArtifact {
     pub count: i32,
 }
 
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct Production {
+    pub map_space_id: i32,
+    pub uncollected: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = production)]
+pub struct NewProduction {
+    pub map_space_id: i32,
+    pub uncollected: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct ArtifactLedgerEntry {
+    pub id: i32,
+    pub from_user_id: Option<i32>,
+    pub from_map_space_id: Option<i32>,
+    pub to_user_id: Option<i32>,
+    pub to_map_space_id: Option<i32>,
+    pub amount: i32,
+    pub reason: ArtifactLedgerReason,
+    pub reference_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = artifact_ledger)]
+pub struct NewArtifactLedgerEntry {
+    pub from_user_id: Option<i32>,
+    pub from_map_space_id: Option<i32>,
+    pub to_user_id: Option<i32>,
+    pub to_map_space_id: Option<i32>,
+    pub amount: i32,
+    pub reason: ArtifactLedgerReason,
+    pub reference_id: Option<i32>,
+}
+
 #[derive(Queryable, Debug, Serialize, Deserialize)]
 pub struct AvailableBlocks {
     pub block_type_id: Option<i32>,
@@ -133,6 +227,11 @@ pub struct Game {
     pub is_game_over: bool,
     pub artifacts_collected: i32,
     pub date: NaiveDate,
+    pub start_time: NaiveDateTime,
+    pub is_revenged: bool,
+    pub is_practice: bool,
+    pub stars: i32,
+    pub challenge_id: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -148,6 +247,9 @@ pub struct NewGame<'a> {
     pub damage_done: &'a i32,
     pub is_game_over: &'a bool,
     pub date: &'a NaiveDate,
+    pub start_time: &'a NaiveDateTime,
+    pub is_practice: &'a bool,
+    pub challenge_id: Option<&'a i32>,
 }
 
 #[derive(Queryable, Serialize)]
@@ -158,6 +260,16 @@ pub struct LevelsFixture {
     pub no_of_bombs: i32,
     pub rating_factor: f32,
     pub no_of_attackers: i32,
+    pub matchmaking_mode: MatchmakingMode,
+    pub rating_system: RatingSystemKind,
+    pub reset_factor: f32,
+    pub is_rolled_over: bool,
+    pub one_star_damage: i32,
+    pub bank_star: bool,
+    pub three_star_damage: i32,
+    pub upgrade_minutes_per_level: i32,
+    pub is_upgrading_defended: bool,
+    pub speed_up_cost_per_minute: i32,
 }
 
 #[derive(Insertable)]
@@ -191,6 +303,7 @@ pub struct MapLayout {
     pub player: i32,
     pub level_id: i32,
     pub is_valid: bool,
+    pub version: i32,
 }
 
 #[derive(Insertable)]
@@ -254,6 +367,10 @@ pub struct User {
     pub trophies: i32,
     pub avatar_id: i32,
     pub artifacts: i32,
+    pub shield_until: Option<NaiveDateTime>,
+    pub rating_deviation: f32,
+    pub rating_volatility: f32,
+    pub builders: i32,
 }
 
 #[derive(Insertable, Debug)]
@@ -339,3 +456,130 @@ pub struct AttackerType {
     pub cost: i32,
     pub name: String,
 }
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct Challenge {
+    pub id: i32,
+    pub challenger_id: i32,
+    pub opponent_id: i32,
+    pub status: ChallengeStatus,
+    pub trophy_stakes: i32,
+    pub game_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = challenge)]
+pub struct NewChallenge<'a> {
+    pub challenger_id: &'a i32,
+    pub opponent_id: &'a i32,
+    pub trophy_stakes: &'a i32,
+}
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub message: String,
+    pub is_read: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = notification)]
+pub struct NewNotification<'a> {
+    pub user_id: &'a i32,
+    pub message: &'a str,
+}
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct RatingHistory {
+    pub id: i32,
+    pub user_id: i32,
+    pub old_rating: i32,
+    pub new_rating: i32,
+    pub reason: RatingChangeReason,
+    pub game_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = rating_history)]
+pub struct NewRatingHistory {
+    pub user_id: i32,
+    pub old_rating: i32,
+    pub new_rating: i32,
+    pub reason: RatingChangeReason,
+    pub game_id: Option<i32>,
+}
+
+#[derive(Queryable, Insertable, Serialize, Clone, Debug)]
+#[diesel(table_name = season_result)]
+pub struct SeasonResult {
+    pub level_id: i32,
+    pub user_id: i32,
+    pub final_rank: i32,
+    pub final_trophies: i32,
+    pub reset_trophies: i32,
+    pub reward_artifacts: i32,
+}
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct League {
+    pub id: i32,
+    pub name: String,
+    pub division: i32,
+    pub min_trophies: i32,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+pub struct UpgradeQueueEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub category: ItemCategory,
+    pub from_id: i32,
+    pub to_id: i32,
+    pub cost: i32,
+    pub started_at: NaiveDateTime,
+    pub finishes_at: NaiveDateTime,
+    pub is_complete: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = upgrade_queue)]
+pub struct NewUpgradeQueueEntry {
+    pub user_id: i32,
+    pub category: ItemCategory,
+    pub from_id: i32,
+    pub to_id: i32,
+    pub cost: i32,
+    pub started_at: NaiveDateTime,
+    pub finishes_at: NaiveDateTime,
+    pub is_complete: bool,
+}
+
+#[derive(Queryable, Serialize, Clone, Debug)]
+pub struct Unlockable {
+    pub id: i32,
+    pub category: ItemCategory,
+    pub block_type_id: Option<i32>,
+    pub attacker_type_id: Option<i32>,
+    pub emp_type_id: Option<i32>,
+    pub cost: i32,
+    pub min_trophies: i32,
+    pub min_bank_level: i32,
+    pub is_active: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = unlockable)]
+pub struct NewUnlockable {
+    pub category: ItemCategory,
+    pub block_type_id: Option<i32>,
+    pub attacker_type_id: Option<i32>,
+    pub emp_type_id: Option<i32>,
+    pub cost: i32,
+    pub min_trophies: i32,
+    pub min_bank_level: i32,
+    pub is_active: bool,
+}
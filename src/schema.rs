@@ -1,13 +1,33 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "artifact_ledger_reason"))]
+    pub struct ArtifactLedgerReason;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "block_category"))]
     pub struct BlockCategory;
 
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "challenge_status"))]
+    pub struct ChallengeStatus;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "item_category"))]
     pub struct ItemCategory;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "matchmaking_mode"))]
+    pub struct MatchmakingMode;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "rating_change_reason"))]
+    pub struct RatingChangeReason;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "rating_system"))]
+    pub struct RatingSystem;
 }
 
 diesel::table! {
@@ -17,6 +37,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ArtifactLedgerReason;
+
+    artifact_ledger (id) {
+        id -> Int4,
+        from_user_id -> Nullable<Int4>,
+        from_map_space_id -> Nullable<Int4>,
+        to_user_id -> Nullable<Int4>,
+        to_map_space_id -> Nullable<Int4>,
+        amount -> Int4,
+        reason -> ArtifactLedgerReason,
+        reference_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     attack_type (id) {
         id -> Int4,
@@ -75,6 +112,22 @@ diesel::table! {
         level -> Int4,
         cost -> Int4,
         hp -> Int4,
+        production_rate -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ChallengeStatus;
+
+    challenge (id) {
+        id -> Int4,
+        challenger_id -> Int4,
+        opponent_id -> Int4,
+        status -> ChallengeStatus,
+        trophy_stakes -> Int4,
+        game_id -> Nullable<Int4>,
+        created_at -> Timestamp,
     }
 }
 
@@ -115,6 +168,20 @@ diesel::table! {
         is_game_over -> Bool,
         artifacts_collected -> Int4,
         date -> Date,
+        start_time -> Timestamp,
+        is_revenged -> Bool,
+        is_practice -> Bool,
+        stars -> Int4,
+        challenge_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    league (id) {
+        id -> Int4,
+        name -> Varchar,
+        division -> Int4,
+        min_trophies -> Int4,
     }
 }
 
@@ -127,6 +194,10 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::MatchmakingMode;
+    use super::sql_types::RatingSystem;
+
     levels_fixture (id) {
         id -> Int4,
         start_date -> Timestamp,
@@ -134,6 +205,16 @@ diesel::table! {
         no_of_bombs -> Int4,
         rating_factor -> Float4,
         no_of_attackers -> Int4,
+        matchmaking_mode -> MatchmakingMode,
+        rating_system -> RatingSystem,
+        reset_factor -> Float4,
+        is_rolled_over -> Bool,
+        one_star_damage -> Int4,
+        bank_star -> Bool,
+        three_star_damage -> Int4,
+        upgrade_minutes_per_level -> Int4,
+        is_upgrading_defended -> Bool,
+        speed_up_cost_per_minute -> Int4,
     }
 }
 
@@ -143,6 +224,7 @@ diesel::table! {
         player -> Int4,
         level_id -> Int4,
         is_valid -> Bool,
+        version -> Int4,
     }
 }
 
@@ -167,6 +249,50 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    notification (id) {
+        id -> Int4,
+        user_id -> Int4,
+        message -> Text,
+        is_read -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    production (map_space_id) {
+        map_space_id -> Int4,
+        uncollected -> Int4,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::RatingChangeReason;
+
+    rating_history (id) {
+        id -> Int4,
+        user_id -> Int4,
+        old_rating -> Int4,
+        new_rating -> Int4,
+        reason -> RatingChangeReason,
+        game_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    season_result (level_id, user_id) {
+        level_id -> Int4,
+        user_id -> Int4,
+        final_rank -> Int4,
+        final_trophies -> Int4,
+        reset_trophies -> Int4,
+        reward_artifacts -> Int4,
+    }
+}
+
 diesel::table! {
     shortest_path (base_id, source_x, source_y, dest_x, dest_y) {
         base_id -> Int4,
@@ -186,6 +312,40 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ItemCategory;
+
+    unlockable (id) {
+        id -> Int4,
+        category -> ItemCategory,
+        block_type_id -> Nullable<Int4>,
+        attacker_type_id -> Nullable<Int4>,
+        emp_type_id -> Nullable<Int4>,
+        cost -> Int4,
+        min_trophies -> Int4,
+        min_bank_level -> Int4,
+        is_active -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ItemCategory;
+
+    upgrade_queue (id) {
+        id -> Int4,
+        user_id -> Int4,
+        category -> ItemCategory,
+        from_id -> Int4,
+        to_id -> Int4,
+        cost -> Int4,
+        started_at -> Timestamp,
+        finishes_at -> Timestamp,
+        is_complete -> Bool,
+    }
+}
+
 diesel::table! {
     user (id) {
         id -> Int4,
@@ -198,6 +358,10 @@ diesel::table! {
         trophies -> Int4,
         avatar_id -> Int4,
         artifacts -> Int4,
+        shield_until -> Nullable<Timestamp>,
+        rating_deviation -> Float4,
+        rating_volatility -> Float4,
+        builders -> Int4,
     }
 }
 
@@ -209,6 +373,7 @@ diesel::joinable!(available_blocks -> user (user_id));
 diesel::joinable!(block_type -> building_type (building_type));
 diesel::joinable!(block_type -> defender_type (defender_type));
 diesel::joinable!(block_type -> mine_type (mine_type));
+diesel::joinable!(challenge -> game (game_id));
 diesel::joinable!(game -> map_layout (map_layout_id));
 diesel::joinable!(level_constraints -> block_type (block_id));
 diesel::joinable!(level_constraints -> levels_fixture (level_id));
@@ -216,25 +381,44 @@ diesel::joinable!(map_layout -> levels_fixture (level_id));
 diesel::joinable!(map_layout -> user (player));
 diesel::joinable!(map_spaces -> block_type (block_type_id));
 diesel::joinable!(map_spaces -> map_layout (map_id));
+diesel::joinable!(notification -> user (user_id));
+diesel::joinable!(production -> map_spaces (map_space_id));
+diesel::joinable!(rating_history -> game (game_id));
+diesel::joinable!(rating_history -> user (user_id));
+diesel::joinable!(season_result -> levels_fixture (level_id));
+diesel::joinable!(season_result -> user (user_id));
 diesel::joinable!(shortest_path -> map_layout (base_id));
 diesel::joinable!(simulation_log -> game (game_id));
+diesel::joinable!(unlockable -> attacker_type (attacker_type_id));
+diesel::joinable!(unlockable -> block_type (block_type_id));
+diesel::joinable!(unlockable -> emp_type (emp_type_id));
+diesel::joinable!(upgrade_queue -> user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     artifact,
+    artifact_ledger,
     attack_type,
     attacker_type,
     available_blocks,
     block_type,
     building_type,
+    challenge,
     defender_type,
     emp_type,
     game,
+    league,
     level_constraints,
     levels_fixture,
     map_layout,
     map_spaces,
     mine_type,
+    notification,
+    production,
+    rating_history,
+    season_result,
     shortest_path,
     simulation_log,
+    unlockable,
+    upgrade_queue,
     user,
 );
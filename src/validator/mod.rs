@@ -6,16 +6,18 @@ use crate::{
         util::{Direction, EventResponse, GameLog},
     },
     models::AttackerType,
-    validator::util::{Coords, SourceDestXY},
+    validator::util::Coords,
 };
 use anyhow::{Ok, Result};
 
 use self::{
+    pathing::Pathing,
     state::State,
     util::{send_terminate_game_message, Attacker, BombType, DefenderReturnType, MineDetails},
 };
 
 pub mod error;
+pub mod pathing;
 pub mod state;
 pub mod util;
 
@@ -23,7 +25,7 @@ pub fn game_handler(
     attacker_type: &HashMap<i32, AttackerType>,
     socket_request: SocketRequest,
     _game_state: &mut State,
-    _shortest_path: &HashMap<SourceDestXY, Coords>,
+    _shortest_path: &impl Pathing,
     _roads: &HashSet<(i32, i32)>,
     _bomb_types: &Vec<BombType>,
     mut _game_log: &mut GameLog,
@@ -0,0 +1,103 @@
+use crate::constants::MAP_SIZE;
+use crate::validator::util::Coords;
+
+const NO_ROAD: u16 = u16::MAX;
+
+pub trait Pathing {
+    // Next road tile on a shortest path from source to dest, None if dest is unreachable
+    fn next_hop(&self, source: Coords, dest: Coords) -> Option<Coords>;
+}
+
+// Road tiles are indexed 0..n and next hops are stored in an n x n matrix of road indices
+#[derive(Clone)]
+pub struct DenseNextHops {
+    tile_index: Vec<u16>,
+    roads: Vec<Coords>,
+    next_hops: Vec<u16>,
+}
+
+impl DenseNextHops {
+    pub fn new(roads: Vec<Coords>) -> DenseNextHops {
+        let mut tile_index = vec![NO_ROAD; MAP_SIZE * MAP_SIZE];
+        for (index, road) in roads.iter().enumerate() {
+            tile_index[road.x as usize * MAP_SIZE + road.y as usize] = index as u16;
+        }
+        let next_hops = vec![NO_ROAD; roads.len() * roads.len()];
+
+        DenseNextHops {
+            tile_index,
+            roads,
+            next_hops,
+        }
+    }
+
+    pub fn roads(&self) -> &[Coords] {
+        &self.roads
+    }
+
+    pub fn index_of(&self, tile: Coords) -> Option<usize> {
+        if tile.x < 0 || tile.y < 0 || tile.x as usize >= MAP_SIZE || tile.y as usize >= MAP_SIZE {
+            return None;
+        }
+        match self.tile_index[tile.x as usize * MAP_SIZE + tile.y as usize] {
+            NO_ROAD => None,
+            index => Some(index as usize),
+        }
+    }
+
+    pub fn set_next_hop(&mut self, source: usize, dest: usize, next_hop: usize) {
+        let roads_count = self.roads.len();
+        self.next_hops[source * roads_count + dest] = next_hop as u16;
+    }
+
+    // Layout: road count (u16), roads as (x, y) byte pairs, then the next hop matrix (u16 each)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(2 + self.roads.len() * 2 + self.next_hops.len() * 2);
+        encoded.extend_from_slice(&(self.roads.len() as u16).to_le_bytes());
+        for road in &self.roads {
+            encoded.push(road.x as u8);
+            encoded.push(road.y as u8);
+        }
+        for next_hop in &self.next_hops {
+            encoded.extend_from_slice(&next_hop.to_le_bytes());
+        }
+        encoded
+    }
+
+    pub fn decode(encoded: &[u8]) -> Option<DenseNextHops> {
+        if encoded.len() < 2 {
+            return None;
+        }
+        let roads_count = u16::from_le_bytes([encoded[0], encoded[1]]) as usize;
+        if encoded.len() != 2 + roads_count * 2 + roads_count * roads_count * 2 {
+            return None;
+        }
+        let (roads_bytes, next_hops_bytes) = encoded[2..].split_at(roads_count * 2);
+
+        let roads = roads_bytes
+            .chunks_exact(2)
+            .map(|road| Coords {
+                x: road[0] as i32,
+                y: road[1] as i32,
+            })
+            .collect();
+        let mut dense_next_hops = DenseNextHops::new(roads);
+        dense_next_hops.next_hops = next_hops_bytes
+            .chunks_exact(2)
+            .map(|next_hop| u16::from_le_bytes([next_hop[0], next_hop[1]]))
+            .collect();
+
+        Some(dense_next_hops)
+    }
+}
+
+impl Pathing for DenseNextHops {
+    fn next_hop(&self, source: Coords, dest: Coords) -> Option<Coords> {
+        let source = self.index_of(source)?;
+        let dest = self.index_of(dest)?;
+        match self.next_hops[source * self.roads.len() + dest] {
+            NO_ROAD => None,
+            next_hop => Some(self.roads[next_hop as usize]),
+        }
+    }
+}
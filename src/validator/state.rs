@@ -1,19 +1,17 @@
-use std::{
-    cmp::max,
-    collections::{HashMap, HashSet},
-};
+use std::{cmp::max, collections::HashSet};
 
 use crate::constants::{BOMB_DAMAGE_MULTIPLIER, LIVES, PERCENTANGE_ARTIFACTS_OBTAINABLE};
 use crate::{
     api::attack::socket::{BuildingResponse, DefenderResponse},
     validator::util::{
         Attacker, BuildingDetails, Coords, DefenderDetails, DefenderReturnType, InValidation,
-        MineDetails, SourceDestXY,
+        MineDetails,
     },
 };
 
 use serde::{Deserialize, Serialize};
 
+use super::pathing::Pathing;
 use super::util::BombType;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -251,7 +249,7 @@ impl State {
     pub fn defender_movement(
         &mut self,
         attacker_delta: Vec<Coords>,
-        shortest_path: &HashMap<SourceDestXY, Coords>,
+        pathing: &impl Pathing,
     ) -> DefenderReturnType {
         let attacker = self.attacker.as_mut().unwrap();
         let mut defenders_damaged: Vec<DefenderResponse> = Vec::new();
@@ -284,14 +282,9 @@ impl State {
 
             // for every tile of defender's movement
             for i in 1..=defender.speed {
-                let next_hop = shortest_path
-                    .get(&SourceDestXY {
-                        source_x: defender.defender_pos.x,
-                        source_y: defender.defender_pos.y,
-                        dest_x: attacker.attacker_pos.x,
-                        dest_y: attacker.attacker_pos.y,
-                    })
-                    .unwrap_or(&defender.defender_pos);
+                let next_hop = pathing
+                    .next_hop(defender.defender_pos, attacker.attacker_pos)
+                    .unwrap_or(defender.defender_pos);
 
                 let mut attacker_tiles_covered_fract = (((i - 1) as f32) * attacker_ratio).fract();
 
@@ -334,7 +327,7 @@ impl State {
                     defender.path_in_current_frame.push(defender.defender_pos);
                     continue;
                 }
-                defender.defender_pos = *next_hop;
+                defender.defender_pos = next_hop;
                 defender.path_in_current_frame.push(defender.defender_pos);
 
                 // if defender and attacker are on the same tile, add the defender to the collision_array
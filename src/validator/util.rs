@@ -3,14 +3,6 @@ use crate::api::attack::socket::{ResultType, SocketResponse};
 use crate::validator::state::State;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Eq, Hash, PartialEq, Serialize, Clone)]
-pub struct SourceDestXY {
-    pub source_x: i32,
-    pub source_y: i32,
-    pub dest_x: i32,
-    pub dest_y: i32,
-}
-
 #[derive(Serialize, Clone, Copy, Deserialize)]
 pub struct Bomb {
     pub id: i32,